@@ -0,0 +1,140 @@
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use dirs::home_dir;
+use log::error;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+
+use crate::errors::Result;
+
+const CONFIG_FILE: &'static str = "config.toml";
+pub const PROJECT_CONFIG_FILE: &'static str = "gdt.toml";
+/// Upper bound on `remote.retries`, the backoff doubles with every retry
+const MAX_RETRIES: u32 = 10;
+
+/// User wide settings, read from `~/.config/gdt/config.toml`
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub remote: RemoteConfig,
+}
+
+/// Settings for fetching remote packages
+///
+/// ```toml
+/// [remote]
+/// timeout = 30         # seconds per request
+/// connect_timeout = 10 # seconds to establish a connection
+/// retries = 3          # retries per url on transient failures, at most 10
+/// backoff = 500        # milliseconds, doubled for every retry
+/// max_file_size = 10485760      # bytes
+/// max_package_size = 104857600  # bytes
+/// allow_cross_host_redirects = false
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct RemoteConfig {
+    pub timeout: u64,
+    pub connect_timeout: u64,
+    pub retries: u32,
+    pub backoff: u64,
+    pub max_file_size: u64,
+    pub max_package_size: u64,
+    pub allow_cross_host_redirects: bool,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 30,
+            connect_timeout: 10,
+            retries: 3,
+            backoff: 500,
+            max_file_size: 10 * 1024 * 1024,
+            max_package_size: 100 * 1024 * 1024,
+            allow_cross_host_redirects: false,
+        }
+    }
+}
+
+/// Project settings, read from `gdt.toml` in the project root
+///
+/// ```toml
+/// strict = true      # refuse unsigned packages and packages signed by untrusted keys
+/// namespaced = true  # install packages to pack/<author>/<name>
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub strict: bool,
+    pub namespaced: bool,
+}
+
+pub fn project_config_path() -> PathBuf {
+    PathBuf::from(PROJECT_CONFIG_FILE)
+}
+
+pub fn config_dir() -> PathBuf {
+    home_dir().unwrap().join(".config/gdt")
+}
+
+/// Load the user config, falling back to the defaults
+/// if the file is missing or invalid
+pub fn load() -> Config {
+    let mut config: Config = load_file(config_dir().join(CONFIG_FILE));
+    if config.remote.retries > MAX_RETRIES {
+        eprintln!("remote.retries is limited to {}", MAX_RETRIES);
+        config.remote.retries = MAX_RETRIES;
+    }
+    config
+}
+
+/// Load the config of the project in the current directory
+pub fn load_project() -> ProjectConfig {
+    load_file(project_config_path())
+}
+
+/// Set a top level flag in the project config, keeping the rest of the file
+pub fn set_project_flag(key: &str, value: bool) -> Result<()> {
+    let path = project_config_path();
+    let data = match path.exists() {
+        true => read_to_string(&path)?,
+        false => String::new(),
+    };
+
+    let entry = format!("{} = {}", key, value);
+    let existing = Regex::new(&format!(r"(?m)^{}\s*=.*$", regex::escape(key))).unwrap();
+    let data = match existing.is_match(&data) {
+        true => existing.replace(&data, entry.as_str()).into_owned(),
+        // Top level keys have to come before any table
+        false => format!("{}\n{}", entry, data),
+    };
+
+    File::create(&path)?.write_all(data.as_bytes())?;
+    Ok(())
+}
+
+fn load_file<T: DeserializeOwned + Default>(path: PathBuf) -> T {
+    if !path.exists() {
+        return T::default();
+    }
+
+    let data = match read_to_string(&path) {
+        Ok(d) => d,
+        Err(e) => {
+            error!("failed to read {:?}: {:?}", path, e);
+            return T::default();
+        }
+    };
+
+    match toml::from_str::<T>(&data) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid config {:?}: {}", path, e);
+            T::default()
+        }
+    }
+}
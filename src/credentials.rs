@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::read_to_string;
+
+use log::error;
+use reqwest::blocking::RequestBuilder;
+use serde_derive::Deserialize;
+
+use crate::config::config_dir;
+
+const CREDENTIALS_FILE: &'static str = "credentials.toml";
+
+/// Per host credentials for private package sources.
+///
+/// Read from `~/.config/gdt/credentials.toml`:
+///
+/// ```toml
+/// [hosts."git.example.com"]
+/// token = "..."
+///
+/// [hosts."packages.example.com"]
+/// username = "..."
+/// password = "..."
+/// ```
+///
+/// and from the environment, where the host is upper cased with every
+/// non alphanumeric character replaced by `_`, e.g.
+/// `GDT_TOKEN_GIT_EXAMPLE_COM` or `GDT_USERNAME_PACKAGES_EXAMPLE_COM` and
+/// `GDT_PASSWORD_PACKAGES_EXAMPLE_COM`. The environment takes precedence.
+#[derive(Deserialize, Debug, Default)]
+pub struct Credentials {
+    #[serde(default)]
+    hosts: HashMap<String, Credential>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Credential {
+    Token { token: String },
+    Basic { username: String, password: Option<String> },
+}
+
+// Never print the secrets themselves
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Token { .. } => write!(f, "Token(***)"),
+            Self::Basic { username, .. } => write!(f, "Basic({}, ***)", username),
+        }
+    }
+}
+
+impl Credentials {
+    /// Attach the credentials for `host`, if any, to the request
+    pub fn apply(&self, host: &str, request: RequestBuilder) -> RequestBuilder {
+        match from_env(host).as_ref().or_else(|| self.hosts.get(host)) {
+            Some(Credential::Token { token }) => request.bearer_auth(token),
+            Some(Credential::Basic { username, password }) => request.basic_auth(username, password.as_ref()),
+            None => request,
+        }
+    }
+}
+
+fn from_env(host: &str) -> Option<Credential> {
+    let suffix = host
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect::<String>();
+
+    if let Ok(token) = env::var(format!("GDT_TOKEN_{}", suffix)) {
+        return Some(Credential::Token { token });
+    }
+
+    match env::var(format!("GDT_USERNAME_{}", suffix)) {
+        Ok(username) => Some(Credential::Basic {
+            username,
+            password: env::var(format!("GDT_PASSWORD_{}", suffix)).ok(),
+        }),
+        Err(_) => None,
+    }
+}
+
+/// Load the credentials file, if there is one
+pub fn load() -> Credentials {
+    let path = config_dir().join(CREDENTIALS_FILE);
+    if !path.exists() {
+        return Credentials::default();
+    }
+
+    let data = match read_to_string(&path) {
+        Ok(d) => d,
+        Err(e) => {
+            error!("failed to read {:?}: {:?}", path, e);
+            return Credentials::default();
+        }
+    };
+
+    // The toml error can quote the offending line, so keep it out of the output
+    match toml::from_str::<Credentials>(&data) {
+        Ok(credentials) => credentials,
+        Err(_) => {
+            eprintln!("Invalid credentials file {:?}", path);
+            Credentials::default()
+        }
+    }
+}
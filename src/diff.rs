@@ -0,0 +1,370 @@
+//! Line based diffing and merging
+//!
+//! Lines keep their line endings so merged output can be written back
+//! exactly as it was read.
+
+/// Split text into lines, keeping the trailing `\n` on each line
+pub fn lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            lines.push(&text[start..=i]);
+            start = i + 1;
+        }
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+/// Pairs of indices of the lines in `a` and `b` that make up
+/// the longest common subsequence of the two
+pub fn common_lines(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    // Skip the common prefix and suffix, they're usually most of the file
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    let mut pairs = (0..prefix).map(|i| (i, i)).collect::<Vec<_>>();
+    lcs(a_mid, b_mid, prefix, prefix, &mut pairs);
+    pairs.extend((0..suffix).map(|n| (a.len() - suffix + n, b.len() - suffix + n)));
+    pairs
+}
+
+/// Hirschberg's algorithm, the longest common subsequence in linear space:
+/// split `a` in half, find where the subsequence crosses into the second
+/// half in `b` and solve both halves. Pairs are offset by `a_start` and `b_start`
+fn lcs(a: &[&str], b: &[&str], a_start: usize, b_start: usize, pairs: &mut Vec<(usize, usize)>) {
+    if a.is_empty() || b.is_empty() {
+        return;
+    }
+    if a.len() == 1 {
+        if let Some(j) = b.iter().position(|line| *line == a[0]) {
+            pairs.push((a_start, b_start + j));
+        }
+        return;
+    }
+
+    let mid = a.len() / 2;
+    let forward = lcs_lengths(a[..mid].iter(), b.iter());
+    let backward = lcs_lengths(a[mid..].iter().rev(), b.iter().rev());
+
+    // forward[j] + backward[b.len() - j] is the length when crossing at j
+    let mut split = 0;
+    for j in 0..=b.len() {
+        if forward[j] + backward[b.len() - j] > forward[split] + backward[b.len() - split] {
+            split = j;
+        }
+    }
+
+    lcs(&a[..mid], &b[..split], a_start, b_start, pairs);
+    lcs(&a[mid..], &b[split..], a_start + mid, b_start + split, pairs);
+}
+
+/// The last row of the LCS table, `lengths[j]` is the length of
+/// the LCS of all of `a` and the first `j` lines of `b`
+fn lcs_lengths<'a, A, B>(a: A, b: B) -> Vec<usize>
+where
+    A: Iterator<Item = &'a &'a str>,
+    B: Iterator<Item = &'a &'a str> + Clone,
+{
+    let mut lengths = vec![0; b.clone().count() + 1];
+    for x in a {
+        let mut diagonal = 0;
+        for (j, y) in b.clone().enumerate() {
+            let above = lengths[j + 1];
+            lengths[j + 1] = match x == y {
+                true => diagonal + 1,
+                false => lengths[j + 1].max(lengths[j]),
+            };
+            diagonal = above;
+        }
+    }
+    lengths
+}
+
+pub struct Merge {
+    pub text: String,
+    pub conflicts: usize,
+}
+
+/// Three way merge of `local` and `upstream`, both derived from `base`.
+///
+/// Changes made on only one side are applied, changes made on both sides
+/// are kept between conflict markers.
+pub fn merge3(base: &str, local: &str, upstream: &str) -> Merge {
+    let base = lines(base);
+    let local = lines(local);
+    let upstream = lines(upstream);
+
+    // Map each base line to its matching line on either side
+    let mut to_local = vec![None; base.len()];
+    for (b, l) in common_lines(&base, &local) {
+        to_local[b] = Some(l);
+    }
+    let mut to_upstream = vec![None; base.len()];
+    for (b, u) in common_lines(&base, &upstream) {
+        to_upstream[b] = Some(u);
+    }
+
+    let mut merge = Merge {
+        text: String::new(),
+        conflicts: 0,
+    };
+    let (mut b, mut l, mut u) = (0, 0, 0);
+
+    loop {
+        // The next base line that is unchanged on both sides
+        let stable = (b..base.len()).find_map(|i| match (to_local[i], to_upstream[i]) {
+            (Some(li), Some(ui)) => Some((i, li, ui)),
+            _ => None,
+        });
+
+        let (b_end, l_end, u_end) = match stable {
+            Some(s) => s,
+            None => (base.len(), local.len(), upstream.len()),
+        };
+
+        merge_chunk(&mut merge, &base[b..b_end], &local[l..l_end], &upstream[u..u_end]);
+
+        match stable {
+            Some(_) => {
+                merge.text.push_str(base[b_end]);
+                b = b_end + 1;
+                l = l_end + 1;
+                u = u_end + 1;
+            }
+            None => break,
+        }
+    }
+
+    merge
+}
+
+fn merge_chunk(merge: &mut Merge, base: &[&str], local: &[&str], upstream: &[&str]) {
+    if local == base || local == upstream {
+        push_lines(&mut merge.text, upstream);
+    } else if upstream == base {
+        push_lines(&mut merge.text, local);
+    } else {
+        merge.conflicts += 1;
+        push_line(&mut merge.text, "<<<<<<< local\n");
+        push_lines(&mut merge.text, local);
+        push_line(&mut merge.text, "=======\n");
+        push_lines(&mut merge.text, upstream);
+        push_line(&mut merge.text, ">>>>>>> upstream\n");
+    }
+}
+
+fn push_lines(text: &mut String, lines: &[&str]) {
+    for line in lines {
+        text.push_str(line);
+    }
+}
+
+/// Push a full line, terminating the previous line if it has no line ending
+fn push_line(text: &mut String, line: &str) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(line);
+}
+
+enum Op {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+impl Op {
+    fn in_a(&self) -> bool {
+        match self {
+            Op::Insert(_) => false,
+            _ => true,
+        }
+    }
+
+    fn in_b(&self) -> bool {
+        match self {
+            Op::Delete(_) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Unified diff of `a` and `b` with `context` lines around each change,
+/// empty if the two are the same
+pub fn unified(a_name: &str, b_name: &str, a: &str, b: &str, context: usize) -> String {
+    let a = lines(a);
+    let b = lines(b);
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (ai, bi) in common_lines(&a, &b).into_iter().chain(Some((a.len(), b.len()))) {
+        ops.extend((i..ai).map(Op::Delete));
+        ops.extend((j..bi).map(Op::Insert));
+        if ai < a.len() {
+            ops.push(Op::Equal(ai));
+        }
+        i = ai + 1;
+        j = bi + 1;
+    }
+
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !(op.in_a() && op.in_b()))
+        .map(|(n, _)| n)
+        .collect::<Vec<_>>();
+
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Group changes that are close enough to share their context
+    let mut hunks = Vec::new();
+    let mut start = changes[0];
+    let mut end = changes[0];
+    for &n in &changes[1..] {
+        if n - end > context * 2 {
+            hunks.push((start, end));
+            start = n;
+        }
+        end = n;
+    }
+    hunks.push((start, end));
+
+    let mut out = format!("--- {}\n+++ {}\n", a_name, b_name);
+    for (start, end) in hunks {
+        let start = start.saturating_sub(context);
+        let end = (end + context + 1).min(ops.len());
+        let hunk = &ops[start..end];
+
+        let a_pos = ops[..start].iter().filter(|op| op.in_a()).count();
+        let b_pos = ops[..start].iter().filter(|op| op.in_b()).count();
+        let a_len = hunk.iter().filter(|op| op.in_a()).count();
+        let b_len = hunk.iter().filter(|op| op.in_b()).count();
+
+        out.push_str(&format!("@@ -{} +{} @@\n", hunk_range(a_pos, a_len), hunk_range(b_pos, b_len)));
+        for op in hunk {
+            match *op {
+                Op::Equal(ai) => push_diff_line(&mut out, ' ', a[ai]),
+                Op::Delete(ai) => push_diff_line(&mut out, '-', a[ai]),
+                Op::Insert(bi) => push_diff_line(&mut out, '+', b[bi]),
+            }
+        }
+    }
+
+    out
+}
+
+/// `start,len` where start is one based, or the line before
+/// the hunk if it has no lines on this side
+fn hunk_range(lines_before: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", lines_before),
+        _ => format!("{},{}", lines_before + 1, len),
+    }
+}
+
+fn push_diff_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_lines_of_changed_middle() {
+        let a = lines("a\nb\nc\nd\ne\n");
+        let b = lines("a\nc\nx\nd\ne\n");
+        assert_eq!(common_lines(&a, &b), vec![(0, 0), (2, 1), (3, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn common_lines_without_common_lines() {
+        let a = lines("a\nb\n");
+        let b = lines("c\nd\n");
+        assert_eq!(common_lines(&a, &b), vec![]);
+    }
+
+    #[test]
+    fn clean_merge_applies_both_sides() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let local = "one\nTWO\nthree\nfour\n";
+        let upstream = "one\ntwo\nthree\nFOUR\n";
+
+        let merge = merge3(base, local, upstream);
+        assert_eq!(merge.conflicts, 0);
+        assert_eq!(merge.text, "one\nTWO\nthree\nFOUR\n");
+    }
+
+    #[test]
+    fn same_change_on_both_sides_is_clean() {
+        let merge = merge3("a\nb\n", "a\nc\n", "a\nc\n");
+        assert_eq!(merge.conflicts, 0);
+        assert_eq!(merge.text, "a\nc\n");
+    }
+
+    #[test]
+    fn conflicting_changes_are_marked() {
+        let merge = merge3("a\nb\nc\n", "a\nlocal\nc\n", "a\nupstream\nc\n");
+        assert_eq!(merge.conflicts, 1);
+        assert_eq!(merge.text, "a\n<<<<<<< local\nlocal\n=======\nupstream\n>>>>>>> upstream\nc\n");
+    }
+
+    #[test]
+    fn insertions_at_the_end() {
+        let merge = merge3("a\nb\n", "a\nb\nlocal\n", "a\nb\n");
+        assert_eq!(merge.conflicts, 0);
+        assert_eq!(merge.text, "a\nb\nlocal\n");
+
+        let merge = merge3("a\nb\n", "a\nb\nlocal\n", "a\nb\nupstream\n");
+        assert_eq!(merge.conflicts, 1);
+        assert_eq!(merge.text, "a\nb\n<<<<<<< local\nlocal\n=======\nupstream\n>>>>>>> upstream\n");
+    }
+
+    #[test]
+    fn conflict_markers_follow_a_missing_line_ending() {
+        let merge = merge3("a\nb", "a\nlocal", "a\nupstream");
+        assert_eq!(merge.conflicts, 1);
+        assert_eq!(merge.text, "a\n<<<<<<< local\nlocal\n=======\nupstream\n>>>>>>> upstream\n");
+    }
+
+    #[test]
+    fn unified_diff_of_a_change() {
+        let diff = unified("a/f.gd", "b/f.gd", "1\n2\n3\n4\n5\n", "1\n2\nthree\n4\n5\n", 1);
+        assert_eq!(diff, "--- a/f.gd\n+++ b/f.gd\n@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n");
+    }
+
+    #[test]
+    fn unified_diff_of_an_insertion_at_the_end() {
+        let diff = unified("a", "b", "1\n", "1\n2\n", 3);
+        assert_eq!(diff, "--- a\n+++ b\n@@ -1,1 +1,2 @@\n 1\n+2\n");
+    }
+
+    #[test]
+    fn unified_diff_of_equal_text_is_empty() {
+        assert_eq!(unified("a", "b", "same\n", "same\n", 3), "");
+    }
+
+    #[test]
+    fn missing_line_ending_is_marked() {
+        let diff = unified("a", "b", "1\n", "1\n2", 0);
+        assert_eq!(diff, "--- a\n+++ b\n@@ -1,0 +2,1 @@\n+2\n\\ No newline at end of file\n");
+    }
+}
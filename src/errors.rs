@@ -1,15 +1,80 @@
+use std::fmt;
 use std::io;
 
+use crate::remote::redact;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
     Toml(toml::de::Error),
+    TomlSer(toml::ser::Error),
     Reqwest(reqwest::Error),
     Failure(failure::Error),
-    FileNotFound(String),
-    InvalidResponse,
+    Http { url: String, status: u16 },
+    /// Reading a response body failed, such as when the connection drops
+    Body { url: String, error: io::Error },
+    InvalidUrl(String),
+    InvalidSource(String),
+    Insecure(String),
+    Redirect { from: String, to: String },
+    FileTooLarge { url: String, limit: u64 },
+    PackageTooLarge { limit: u64 },
+    NoSources,
+    InvalidKey,
+    InvalidSignature,
+    Unsigned(String),
+    UntrustedKey(String),
+    HashMismatch(String),
+    UnlistedPattern(String),
+    Parse(String),
+}
+
+impl Error {
+    /// Errors caused by the download policy rather than the source,
+    /// these should not be retried against a mirror
+    pub fn is_policy_violation(&self) -> bool {
+        match self {
+            Self::Insecure(_) | Self::Redirect { .. } | Self::FileTooLarge { .. } | Self::PackageTooLarge { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Toml(e) => write!(f, "{}", e),
+            Self::TomlSer(e) => write!(f, "{}", e),
+            Self::Reqwest(e) => match e.url() {
+                Some(url) => write!(f, "{}", e.to_string().replace(url.as_str(), &redact(url.as_str()))),
+                None => write!(f, "{}", e),
+            },
+            Self::Failure(e) => write!(f, "{}", e),
+            Self::Http { url, status } => write!(f, "{} returned HTTP {}", url, status),
+            Self::Body { url, error } => write!(f, "failed to read {}: {}", url, error),
+            Self::InvalidUrl(url) => write!(f, "invalid url {}", url),
+            Self::InvalidSource(source) => write!(
+                f,
+                "invalid source \"{}\" (expected a path, a url, user/package or the name of an installed package)",
+                source
+            ),
+            Self::Insecure(url) => write!(f, "refusing insecure url {} (use --allow-insecure to allow http)", url),
+            Self::Redirect { from, to } => write!(f, "blocked redirect from {} to {}", from, to),
+            Self::FileTooLarge { url, limit } => write!(f, "{} exceeds the file size limit of {} bytes", url, limit),
+            Self::PackageTooLarge { limit } => write!(f, "package exceeds the size limit of {} bytes", limit),
+            Self::NoSources => write!(f, "no sources to fetch from"),
+            Self::InvalidKey => write!(f, "invalid signing key"),
+            Self::InvalidSignature => write!(f, "invalid signature, the package may have been tampered with"),
+            Self::Unsigned(name) => write!(f, "{} is not signed (strict mode)", name),
+            Self::UntrustedKey(key) => write!(f, "signed by untrusted key {} (strict mode)", key),
+            Self::HashMismatch(file) => write!(f, "{} does not match its signed hash", file),
+            Self::Parse(message) => write!(f, "parse error: {}", message),
+            Self::UnlistedPattern(pattern) => write!(f, "can't expand {}, the files of unsigned remote packages can't be listed", pattern),
+        }
+    }
 }
 
 impl From<io::Error> for Error {
@@ -24,6 +89,12 @@ impl From<toml::de::Error> for Error {
     }
 }
 
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::TomlSer(err)
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Self {
         Self::Reqwest(err)
@@ -0,0 +1,466 @@
+use std::fmt;
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::path::Path;
+
+use crate::errors::Result;
+
+use super::variant::Variant;
+
+/// A file in Godot's ConfigFile format, such as `project.godot`.
+///
+/// The file is kept as it was read, values are only parsed when they are
+/// looked up, so a file that isn't changed is written back byte for byte
+/// and changes leave comments and ordering alone.
+pub struct ConfigFile {
+    items: Vec<Item>,
+}
+
+enum Item {
+    /// Comments, blank lines and anything else that isn't a section or property
+    Text(String),
+    Section { name: String, raw: String },
+    Property {
+        key: String,
+        /// The key as written, up to and including the `=`
+        prefix: String,
+        /// The value as written, possibly spanning several lines
+        value: String,
+        /// The line ending
+        end: String,
+    },
+}
+
+impl Item {
+    fn is_blank(&self) -> bool {
+        match self {
+            Item::Text(text) => text.trim().is_empty(),
+            _ => false,
+        }
+    }
+
+    fn ends_line(&self) -> bool {
+        match self {
+            Item::Text(raw) | Item::Section { raw, .. } => raw.ends_with('\n'),
+            Item::Property { end, .. } => !end.is_empty(),
+        }
+    }
+
+    fn end_line(&mut self) {
+        match self {
+            Item::Text(raw) | Item::Section { raw, .. } => raw.push('\n'),
+            Item::Property { end, .. } => end.push('\n'),
+        }
+    }
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::parse(&read_to_string(path)?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        File::create(path)?.write_all(self.to_string().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut items = Vec::new();
+        let mut pos = 0;
+
+        while pos < text.len() {
+            let line_end = text[pos..].find('\n').map(|i| pos + i + 1).unwrap_or(text.len());
+            let line = &text[pos..line_end];
+            let trimmed = line.trim();
+
+            if trimmed.starts_with('[') && trimmed.contains(']') {
+                let name = &trimmed[1..trimmed.find(']').unwrap()];
+                items.push(Item::Section {
+                    name: name.to_string(),
+                    raw: line.to_string(),
+                });
+            } else if trimmed.is_empty() || trimmed.starts_with(';') || trimmed.starts_with('#') || !trimmed.contains('=') {
+                items.push(Item::Text(line.to_string()));
+            } else {
+                let eq = pos + line.find('=').unwrap();
+                let value_end = value_end(text, eq + 1);
+                let mut value = &text[eq + 1..value_end];
+                let mut end = if text[value_end..].starts_with('\n') { "\n" } else { "" };
+                if value.ends_with('\r') {
+                    value = &value[..value.len() - 1];
+                    end = if end.is_empty() { "\r" } else { "\r\n" };
+                }
+
+                items.push(Item::Property {
+                    key: unquote(text[pos..eq].trim()),
+                    prefix: text[pos..=eq].to_string(),
+                    value: value.to_string(),
+                    end: end.to_string(),
+                });
+                pos = value_end + if text[value_end..].starts_with('\n') { 1 } else { 0 };
+                continue;
+            }
+
+            pos = line_end;
+        }
+
+        Self { items }
+    }
+
+    /// The value of `key` in `section` as it's written, the section
+    /// is empty for the keys before the first section
+    pub fn get_raw(&self, section: &str, key: &str) -> Option<&str> {
+        self.find(section, key).map(|i| match &self.items[i] {
+            Item::Property { value, .. } => value.as_str(),
+            _ => unreachable!(),
+        })
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Result<Option<Variant>> {
+        match self.get_raw(section, key) {
+            Some(raw) => Ok(Some(Variant::parse(raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The keys of `section` in the order they're written
+    pub fn keys(&self, section: &str) -> Vec<&str> {
+        let (start, end) = match self.section_range(section) {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+        self.items[start..end]
+            .iter()
+            .filter_map(|item| match item {
+                Item::Property { key, .. } => Some(key.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The section and key of the property written on `line`, counted from 1,
+    /// values spanning several lines cover all of them
+    pub fn setting_at(&self, line: usize) -> Option<(&str, &str)> {
+        let mut section = "";
+        let mut start = 1;
+        for item in &self.items {
+            let lines = match item {
+                Item::Text(raw) => raw.matches('\n').count(),
+                Item::Section { name, raw } => {
+                    section = name;
+                    raw.matches('\n').count()
+                }
+                Item::Property { key, prefix, value, end } => {
+                    let spanned = prefix.matches('\n').count() + value.matches('\n').count();
+                    if (start..=start + spanned).contains(&line) {
+                        return Some((section, key));
+                    }
+                    spanned + end.matches('\n').count()
+                }
+            };
+            start += lines;
+            if start > line {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Remove `key` from `section`, returns false if it wasn't set. A section
+    /// left with nothing but blank lines is removed too
+    pub fn remove(&mut self, section: &str, key: &str) -> bool {
+        let i = match self.find(section, key) {
+            Some(i) => i,
+            None => return false,
+        };
+        self.items.remove(i);
+
+        if let Some((start, end)) = self.section_range(section) {
+            if !section.is_empty() && self.items[start + 1..end].iter().all(|item| item.is_blank()) {
+                // The last section takes the blank line separating it from the previous one along
+                let start = match end == self.items.len() && start > 0 && self.items[start - 1].is_blank() {
+                    true => start - 1,
+                    false => start,
+                };
+                self.items.drain(start..end);
+            }
+        }
+        true
+    }
+
+    /// Set the value of `key` in `section`, new keys are added at the end
+    /// of the section and new sections at the end of the file
+    pub fn set(&mut self, section: &str, key: &str, value: &Variant) {
+        if let Some(i) = self.find(section, key) {
+            if let Item::Property { value: current, .. } = &mut self.items[i] {
+                *current = value.to_string();
+            }
+            return;
+        }
+
+        let property = Item::Property {
+            key: key.to_string(),
+            prefix: format!("{}=", quote_key(key)),
+            value: value.to_string(),
+            end: "\n".to_string(),
+        };
+
+        let index = match self.section_range(section) {
+            Some((start, end)) => {
+                // After the last property, or after the blank line following the header
+                let last_property = (start..end).rev().find(|&i| match self.items[i] {
+                    Item::Property { .. } => true,
+                    _ => false,
+                });
+                match last_property {
+                    Some(i) => i + 1,
+                    // Top level keys go after the comments at the start of the file
+                    None if section.is_empty() => (start..end)
+                        .rev()
+                        .find(|&i| !self.items[i].is_blank())
+                        .map(|i| i + 1)
+                        .unwrap_or(start),
+                    None => match self.items.get(start + 1) {
+                        Some(item) if item.is_blank() => start + 2,
+                        _ => start + 1,
+                    },
+                }
+            }
+            None => {
+                if let Some(last) = self.items.last() {
+                    if !last.ends_line() {
+                        self.items.last_mut().unwrap().end_line();
+                    }
+                    if !self.items.last().unwrap().is_blank() {
+                        self.items.push(Item::Text("\n".to_string()));
+                    }
+                }
+                self.items.push(Item::Section {
+                    name: section.to_string(),
+                    raw: format!("[{}]\n", section),
+                });
+                self.items.push(Item::Text("\n".to_string()));
+                self.items.len()
+            }
+        };
+
+        if index > 0 && !self.items[index - 1].ends_line() {
+            self.items[index - 1].end_line();
+        }
+        self.items.insert(index, property);
+    }
+
+    /// The index of `key` in `section`
+    fn find(&self, section: &str, key: &str) -> Option<usize> {
+        let (start, end) = self.section_range(section)?;
+        (start..end).find(|&i| match &self.items[i] {
+            Item::Property { key: k, .. } => k == key,
+            _ => false,
+        })
+    }
+
+    /// The items of a section, starting with its header
+    fn section_range(&self, section: &str) -> Option<(usize, usize)> {
+        let start = match section.is_empty() {
+            true => 0,
+            false => self.items.iter().position(|item| match item {
+                Item::Section { name, .. } => name == section,
+                _ => false,
+            })?,
+        };
+
+        let end = self.items[start..]
+            .iter()
+            .enumerate()
+            .skip(if section.is_empty() { 0 } else { 1 })
+            .find(|(_, item)| match item {
+                Item::Section { .. } => true,
+                _ => false,
+            })
+            .map(|(i, _)| start + i)
+            .unwrap_or(self.items.len());
+
+        Some((start, end))
+    }
+}
+
+impl fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                Item::Text(raw) | Item::Section { raw, .. } => write!(f, "{}", raw)?,
+                Item::Property { prefix, value, end, .. } => write!(f, "{}{}{}", prefix, value, end)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The end of a value starting at `start`, the first line break
+/// outside of a string, array, dictionary or constructor
+fn value_end(text: &str, start: usize) -> usize {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '[' | '{' | '(' => depth += 1,
+            ']' | '}' | ')' => depth -= 1,
+            '\n' if depth <= 0 => return start + i,
+            _ => {}
+        }
+    }
+
+    text.len()
+}
+
+fn unquote(key: &str) -> String {
+    match key.len() > 1 && key.starts_with('"') && key.ends_with('"') {
+        true => key[1..key.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\"),
+        false => key.to_string(),
+    }
+}
+
+/// Keys with anything but plain characters are written quoted, like Godot does
+fn quote_key(key: &str) -> String {
+    let plain = key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '/' || c == '.' || c == '-');
+    match plain {
+        true => key.to_string(),
+        false => format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &'static str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/project.godot"));
+
+    #[test]
+    fn unchanged_file_is_written_back_as_it_was() {
+        assert_eq!(ConfigFile::parse(PROJECT).to_string(), PROJECT);
+    }
+
+    #[test]
+    fn crlf_file_is_written_back_as_it_was() {
+        let text = PROJECT.replace('\n', "\r\n");
+        assert_eq!(ConfigFile::parse(&text).to_string(), text);
+    }
+
+    #[test]
+    fn values_are_read_by_section() {
+        let project = ConfigFile::parse(PROJECT);
+        assert_eq!(project.get_raw("", "config_version"), Some("4"));
+        assert_eq!(project.get("application", "config/name").unwrap(), Some(Variant::String("My \"Game\"".to_string())));
+        assert_eq!(project.get("autoload", "config/name").unwrap(), None);
+        assert_eq!(project.keys("display"), vec!["window/size/width", "window/stretch/aspect"]);
+    }
+
+    #[test]
+    fn multi_line_values_are_read_whole() {
+        let project = ConfigFile::parse(PROJECT);
+        let jump = project.get("input", "jump").unwrap().unwrap();
+        match jump.get("events") {
+            Some(Variant::Array(events)) => assert_eq!(events.len(), 2),
+            other => panic!("unexpected events {:?}", other),
+        }
+
+        let classes = project.get("", "_global_script_classes").unwrap().unwrap();
+        match classes {
+            Variant::Array(classes) => assert_eq!(classes[0].get("class"), Some(&Variant::String("Foo".to_string()))),
+            other => panic!("unexpected classes {:?}", other),
+        }
+    }
+
+    #[test]
+    fn settings_are_found_by_line() {
+        let project = ConfigFile::parse(PROJECT);
+        assert_eq!(project.setting_at(9), Some(("", "config_version")));
+        assert_eq!(project.setting_at(15), Some(("", "_global_script_classes")));
+        assert_eq!(project.setting_at(18), Some(("", "_global_script_class_icons")));
+        assert_eq!(project.setting_at(24), Some(("application", "run/main_scene")));
+        assert_eq!(project.setting_at(29), Some(("autoload", "Global")));
+        assert_eq!(project.setting_at(21), None);
+        assert_eq!(project.setting_at(100), None);
+    }
+
+    #[test]
+    fn parsed_values_are_written_the_way_godot_writes_them() {
+        let project = ConfigFile::parse(PROJECT);
+        for section in &["", "application", "editor_plugins", "input", "rendering"] {
+            for key in project.keys(section) {
+                let raw = project.get_raw(section, key).unwrap();
+                assert_eq!(Variant::parse(raw).unwrap().to_string(), raw);
+            }
+        }
+    }
+
+    #[test]
+    fn changing_a_value_leaves_the_rest_alone() {
+        let mut project = ConfigFile::parse(PROJECT);
+        project.set("display", "window/size/width", &Variant::Int(1920));
+        assert_eq!(project.to_string(), PROJECT.replace("window/size/width=1280", "window/size/width=1920"));
+    }
+
+    #[test]
+    fn new_keys_go_after_the_last_key_of_the_section() {
+        let mut project = ConfigFile::parse(PROJECT);
+        project.set("display", "window/vsync/use_vsync", &Variant::Bool(false));
+        let expected = PROJECT.replace(
+            "window/stretch/aspect=\"keep\"\n",
+            "window/stretch/aspect=\"keep\"\nwindow/vsync/use_vsync=false\n",
+        );
+        assert_eq!(project.to_string(), expected);
+    }
+
+    #[test]
+    fn new_sections_go_at_the_end() {
+        let mut project = ConfigFile::parse(PROJECT);
+        project.set("audio", "default_bus_layout", &Variant::String("res://bus.tres".to_string()));
+        assert_eq!(project.to_string(), format!("{}\n[audio]\n\ndefault_bus_layout=\"res://bus.tres\"\n", PROJECT));
+    }
+
+    #[test]
+    fn file_without_trailing_line_break() {
+        let mut project = ConfigFile::parse("[application]\n\nconfig/name=\"a\"");
+        project.set("application", "run/main_scene", &Variant::String("res://Main.tscn".to_string()));
+        assert_eq!(project.to_string(), "[application]\n\nconfig/name=\"a\"\nrun/main_scene=\"res://Main.tscn\"\n");
+    }
+
+    #[test]
+    fn removing_a_key_leaves_the_rest_alone() {
+        let mut project = ConfigFile::parse(PROJECT);
+        assert!(project.remove("application", "config/icon"));
+        assert!(!project.remove("application", "config/icon"));
+        assert_eq!(project.to_string(), PROJECT.replace("config/icon=\"res://icon.png\"\n", ""));
+    }
+
+    #[test]
+    fn removing_the_last_key_removes_the_section() {
+        let mut project = ConfigFile::parse(PROJECT);
+        assert!(project.remove("autoload", "Global"));
+        assert_eq!(project.to_string(), PROJECT.replace("[autoload]\n\nGlobal=\"*res://global.gd\"\n\n", ""));
+
+        let mut project = ConfigFile::parse("[application]\n\nconfig/name=\"a\"\n\n[autoload]\n\nGlobal=\"*res://global.gd\"\n");
+        assert!(project.remove("autoload", "Global"));
+        assert_eq!(project.to_string(), "[application]\n\nconfig/name=\"a\"\n");
+
+        // Keys before the first section have no section to remove
+        let mut project = ConfigFile::parse("config_version=4\n\n[application]\n\nconfig/name=\"a\"\n");
+        assert!(project.remove("", "config_version"));
+        assert_eq!(project.to_string(), "\n[application]\n\nconfig/name=\"a\"\n");
+    }
+
+    #[test]
+    fn quoted_keys() {
+        let mut project = ConfigFile::parse("[input]\n\n\"ui accept\"=1\n");
+        assert_eq!(project.keys("input"), vec!["ui accept"]);
+        project.set("input", "ui \"cancel\"", &Variant::Int(2));
+        assert_eq!(project.to_string(), "[input]\n\n\"ui accept\"=1\n\"ui \\\"cancel\\\"\"=2\n");
+    }
+}
@@ -0,0 +1,93 @@
+use super::variant::Variant;
+
+/// Godot 3 scancodes of the keys that aren't characters, `KEY_ESCAPE` and up
+const SPECIAL_KEYS: [&'static str; 43] = [
+    "Escape", "Tab", "Backtab", "Backspace", "Enter", "KpEnter", "Insert", "Delete", "Pause", "Print", "SysReq", "Clear",
+    "Home", "End", "Left", "Up", "Right", "Down", "PageUp", "PageDown", "Shift", "Control", "Meta", "Alt", "CapsLock",
+    "NumLock", "ScrollLock", "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12", "F13", "F14",
+    "F15", "F16",
+];
+const SPECIAL_KEY_BASE: i64 = 1 << 24;
+const DEFAULT_DEADZONE: f64 = 0.5;
+
+/// The scancode of a key name as the editor shows it, such as `A`, `Space`,
+/// `Left` or `F1`
+pub fn scancode(key: &str) -> Option<i64> {
+    if let Some(i) = SPECIAL_KEYS.iter().position(|name| name.eq_ignore_ascii_case(key)) {
+        return Some(SPECIAL_KEY_BASE + 1 + i as i64);
+    }
+
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        _ if key.eq_ignore_ascii_case("space") => Some(' ' as i64),
+        (Some(c), None) if c.is_ascii_graphic() => Some(c.to_ascii_uppercase() as i64),
+        _ => None,
+    }
+}
+
+/// An `InputEventKey` for a key with optional modifiers, e.g. `Ctrl+S`
+pub fn key_event(key: &str) -> Option<Variant> {
+    let mut parts = key.split('+').collect::<Vec<_>>();
+    let scancode = scancode(parts.pop()?)?;
+    let (mut alt, mut shift, mut control, mut meta) = (false, false, false, false);
+    for modifier in parts {
+        match modifier.to_lowercase().as_str() {
+            "alt" => alt = true,
+            "shift" => shift = true,
+            "ctrl" | "control" => control = true,
+            "meta" | "cmd" | "super" => meta = true,
+            _ => return None,
+        }
+    }
+
+    Some(event(
+        "InputEventKey",
+        vec![
+            ("alt", Variant::Bool(alt)),
+            ("shift", Variant::Bool(shift)),
+            ("control", Variant::Bool(control)),
+            ("meta", Variant::Bool(meta)),
+            ("command", Variant::Bool(control)),
+            ("pressed", Variant::Bool(false)),
+            ("scancode", Variant::Int(scancode)),
+            ("unicode", Variant::Int(0)),
+            ("echo", Variant::Bool(false)),
+        ],
+    ))
+}
+
+/// An `InputEventJoypadButton` for a button index
+pub fn joypad_button_event(button: i64) -> Variant {
+    event(
+        "InputEventJoypadButton",
+        vec![
+            ("button_index", Variant::Int(button)),
+            ("pressure", Variant::Float(0.0)),
+            ("pressed", Variant::Bool(false)),
+        ],
+    )
+}
+
+/// An action of the `[input]` section
+pub fn action(deadzone: Option<f64>, events: Vec<Variant>) -> Variant {
+    Variant::Dictionary(vec![
+        (Variant::String("deadzone".to_string()), Variant::Float(deadzone.unwrap_or(DEFAULT_DEADZONE))),
+        (Variant::String("events".to_string()), Variant::Array(events)),
+    ])
+}
+
+/// Events are written with every property, as the editor writes them
+fn event(class: &str, properties: Vec<(&str, Variant)>) -> Variant {
+    let mut all = vec![
+        ("resource_local_to_scene".to_string(), Variant::Bool(false)),
+        ("resource_name".to_string(), Variant::String(String::new())),
+        ("device".to_string(), Variant::Int(0)),
+    ];
+    all.extend(properties.into_iter().map(|(key, value)| (key.to_string(), value)));
+    all.push(("script".to_string(), Variant::Nil));
+
+    Variant::Object {
+        class: class.to_string(),
+        properties: all,
+    }
+}
@@ -4,13 +4,28 @@ use std::fs::{
     read_dir, ReadDir
 };
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
 
 use dirs::home_dir;
 use handlebars;
 
 use crate::errors::Result;
 
+mod config_file;
+pub mod input;
+mod scene;
+mod script_class;
+mod variant;
+
+pub use config_file::ConfigFile;
+pub use scene::TextResource;
+pub use script_class::ScriptClass;
+pub use variant::Variant;
+
+/// The project settings of a Godot project
+pub const PROJECT_FILE: &'static str = "project.godot";
+
 fn create_project_files(project_root: &PathBuf, template_root: &PathBuf, files: ReadDir, context: &HashMap<&str, &str>) {
     let hb = handlebars::Handlebars::new();
 
@@ -118,6 +133,60 @@ pub fn init(name: String) {
     create_project_files(&project_root, &template_root, files, &context);
 }
 
+/// Print a project setting, `section/key` or just `key` for the
+/// settings before the first section
+pub fn config_get(setting: String) {
+    let project = match ConfigFile::load(Path::new(PROJECT_FILE)) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", PROJECT_FILE, e);
+            process::exit(1);
+        }
+    };
+
+    let (section, key) = split_setting(&setting);
+    match project.get_raw(section, key) {
+        Some(value) => println!("{}", value),
+        None => {
+            eprintln!("{} is not set", setting);
+            process::exit(1);
+        }
+    }
+}
+
+/// Change a project setting. The value is a Godot value such as `true`,
+/// `Vector2( 1, 2 )` or `"text"`, anything else is set as a string
+pub fn config_set(setting: String, value: String) {
+    let path = Path::new(PROJECT_FILE);
+    let mut project = match ConfigFile::load(path) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", PROJECT_FILE, e);
+            process::exit(1);
+        }
+    };
+
+    let value = Variant::parse(&value).unwrap_or(Variant::String(value));
+    let (section, key) = split_setting(&setting);
+    project.set(section, key, &value);
+
+    match project.save(path) {
+        Ok(_) => println!("{}={}", setting, value),
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", PROJECT_FILE, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `application/config/name` is `config/name` in the `application` section
+fn split_setting(setting: &str) -> (&str, &str) {
+    match setting.find('/') {
+        Some(i) => (&setting[..i], &setting[i + 1..]),
+        None => ("", setting),
+    }
+}
+
 fn create_file(path: PathBuf, content: &str) {
     let mut file = match File::create(path) {
         Ok(file) => file,
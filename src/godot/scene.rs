@@ -0,0 +1,157 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::errors::{Error, Result};
+
+use super::variant::Variant;
+
+/// A `[tag key=value ...]` heading of a scene or resource file
+/// and the `key = value` properties below it
+#[derive(Debug)]
+pub struct Section {
+    pub tag: String,
+    pub attributes: Vec<(String, Variant)>,
+    pub properties: Vec<(String, Variant)>,
+    /// Line of the heading, starting at 1
+    pub line: usize,
+}
+
+impl Section {
+    pub fn attribute(&self, key: &str) -> Option<&Variant> {
+        self.attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
+/// A scene (`.tscn`) or resource (`.tres`) in Godot's text format
+#[derive(Debug)]
+pub struct TextResource {
+    pub sections: Vec<Section>,
+}
+
+/// An `ext_resource`, a file the scene or resource depends on
+pub struct ExtResource<'a> {
+    pub path: &'a str,
+    pub line: usize,
+}
+
+impl TextResource {
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut sections: Vec<Section> = Vec::new();
+        let mut pos = 0;
+        let (mut line, mut counted) = (1, 0);
+
+        loop {
+            let rest = &text[pos..];
+            pos += rest.len() - rest.trim_start().len();
+            let rest = &text[pos..];
+            line += text[counted..pos].matches('\n').count();
+            counted = pos;
+
+            if rest.is_empty() {
+                break;
+            } else if rest.starts_with(';') {
+                pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with('[') {
+                let (section, len) = heading(rest, line).map_err(|e| at_line(e, line))?;
+                sections.push(section);
+                pos += len;
+            } else {
+                let eq = match rest.find('=') {
+                    Some(eq) if !rest[..eq].contains('\n') => eq,
+                    _ => return Err(Error::Parse(format!("expected a property on line {}", line))),
+                };
+                let key = unquote(rest[..eq].trim());
+                let (value, len) = Variant::parse_prefix(&rest[eq + 1..]).map_err(|e| at_line(e, line))?;
+
+                match sections.last_mut() {
+                    Some(section) => section.properties.push((key, value)),
+                    None => return Err(Error::Parse(format!("property before the first heading on line {}", line))),
+                }
+                pos += eq + 1 + len;
+            }
+        }
+
+        Ok(Self { sections })
+    }
+
+    /// The `gd_scene` or `gd_resource` heading
+    pub fn header(&self) -> Option<&Section> {
+        self.sections.first().filter(|s| s.tag == "gd_scene" || s.tag == "gd_resource")
+    }
+
+    pub fn ext_resources(&self) -> Vec<ExtResource<'_>> {
+        self.sections
+            .iter()
+            .filter(|section| section.tag == "ext_resource")
+            .filter_map(|section| {
+                Some(ExtResource {
+                    path: section.attribute("path")?.as_str()?,
+                    line: section.line,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parse a `[tag key=value ...]` heading, returns it with its length
+fn heading(text: &str, line: usize) -> Result<(Section, usize)> {
+    let mut pos = 1;
+    let tag = identifier(&text[pos..]);
+    if tag.is_empty() {
+        return Err(Error::Parse("expected a tag".to_string()));
+    }
+    pos += tag.len();
+
+    let mut attributes = Vec::new();
+    loop {
+        let rest = &text[pos..];
+        pos += rest.len() - rest.trim_start().len();
+        let rest = &text[pos..];
+
+        if rest.starts_with(']') {
+            pos += 1;
+            break;
+        }
+
+        let key = identifier(rest);
+        if key.is_empty() || !rest[key.len()..].starts_with('=') {
+            return Err(Error::Parse(format!("expected an attribute in [{}]", tag)));
+        }
+        pos += key.len() + 1;
+
+        let (value, len) = Variant::parse_prefix(&text[pos..])?;
+        attributes.push((key.to_string(), value));
+        pos += len;
+    }
+
+    let section = Section {
+        tag: tag.to_string(),
+        attributes,
+        properties: Vec::new(),
+        line,
+    };
+    Ok((section, pos))
+}
+
+fn identifier(text: &str) -> &str {
+    let len = text.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '/')).unwrap_or(text.len());
+    &text[..len]
+}
+
+fn unquote(key: &str) -> String {
+    match key.len() > 1 && key.starts_with('"') && key.ends_with('"') {
+        true => key[1..key.len() - 1].to_string(),
+        false => key.to_string(),
+    }
+}
+
+fn at_line(error: Error, line: usize) -> Error {
+    match error {
+        Error::Parse(message) => Error::Parse(format!("{} on line {}", message, line)),
+        e => e,
+    }
+}
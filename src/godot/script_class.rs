@@ -0,0 +1,113 @@
+use std::fs::read_to_string;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::project::{res_file, resolve_res_path};
+
+use super::variant::Variant;
+
+/// What a script without `extends` extends
+const DEFAULT_BASE: &'static str = "Reference";
+/// How far `extends "path"` chains are followed to find a base class
+const MAX_EXTENDS_DEPTH: usize = 16;
+const LANGUAGE: &'static str = "GDScript";
+/// The keys of a `_global_script_classes` entry that are read
+const KNOWN_KEYS: [&'static str; 4] = ["base", "class", "language", "path"];
+
+lazy_static! {
+    static ref CLASS_NAME: Regex = Regex::new(r#"(?m)^class_name\s+([A-Za-z_][A-Za-z0-9_]*)(?:\s*,\s*"([^"]*)")?"#).unwrap();
+    static ref EXTENDS: Regex = Regex::new(r#"(?m)^extends\s+(?:"([^"]*)"|'([^']*)'|([A-Za-z_][A-Za-z0-9_.]*))"#).unwrap();
+}
+
+/// A GDScript declaring a `class_name`, an entry of
+/// `_global_script_classes` in `project.godot`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptClass {
+    pub name: String,
+    pub base: String,
+    /// `res://` path of the script
+    pub path: String,
+    pub language: String,
+    /// `res://` path of the icon, empty if there's none
+    pub icon: String,
+    /// Any other keys of the entry, written back as they were
+    pub other: Vec<(Variant, Variant)>,
+}
+
+impl ScriptClass {
+    /// The class declared by the script at `res_path`, if any
+    pub fn load(res_path: &str) -> Option<ScriptClass> {
+        let source = read_to_string(res_file(res_path)).ok()?;
+        let caps = CLASS_NAME.captures(&source)?;
+
+        Some(ScriptClass {
+            name: caps[1].to_string(),
+            base: base_class(res_path, &source, 0),
+            path: res_path.to_string(),
+            language: LANGUAGE.to_string(),
+            icon: caps.get(2).map(|icon| resolve_res_path(res_path, icon.as_str())).unwrap_or_default(),
+            other: Vec::new(),
+        })
+    }
+
+    pub fn from_variant(value: &Variant) -> Option<ScriptClass> {
+        let entries = match value {
+            Variant::Dictionary(entries) => entries,
+            _ => return None,
+        };
+        let field = |key: &str| value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+        let other = entries
+            .iter()
+            .filter(|(key, _)| !key.as_str().map(|key| KNOWN_KEYS.contains(&key)).unwrap_or(false))
+            .cloned()
+            .collect();
+
+        Some(ScriptClass {
+            name: field("class")?,
+            base: field("base").unwrap_or_default(),
+            path: field("path")?,
+            language: field("language").unwrap_or_else(|| LANGUAGE.to_string()),
+            icon: String::new(),
+            other,
+        })
+    }
+
+    /// Written with its keys sorted, like Godot does
+    pub fn to_variant(&self) -> Variant {
+        let entry = |key: &str, value: &str| (Variant::String(key.to_string()), Variant::String(value.to_string()));
+        let mut entries = vec![
+            entry("base", &self.base),
+            entry("class", &self.name),
+            entry("language", &self.language),
+            entry("path", &self.path),
+        ];
+        entries.extend(self.other.iter().cloned());
+        entries.sort_by(|(a, _), (b, _)| a.to_string().cmp(&b.to_string()));
+        Variant::Dictionary(entries)
+    }
+}
+
+/// The class a script extends, for `extends "path"` the class of that
+/// script, or what it extends in turn if it has no `class_name`
+fn base_class(res_path: &str, source: &str, depth: usize) -> String {
+    let caps = match EXTENDS.captures(source) {
+        Some(c) => c,
+        None => return DEFAULT_BASE.to_string(),
+    };
+
+    if let Some(name) = caps.get(3) {
+        return name.as_str().to_string();
+    }
+
+    let parent = resolve_res_path(res_path, caps.get(1).or(caps.get(2)).unwrap().as_str());
+    let parent_source = match (depth < MAX_EXTENDS_DEPTH, read_to_string(res_file(&parent))) {
+        (true, Ok(s)) => s,
+        _ => return DEFAULT_BASE.to_string(),
+    };
+
+    match CLASS_NAME.captures(&parent_source) {
+        Some(parent_class) => parent_class[1].to_string(),
+        None => base_class(&parent, &parent_source, depth + 1),
+    }
+}
@@ -0,0 +1,442 @@
+use std::fmt;
+
+use crate::errors::{Error, Result};
+
+/// A value as Godot writes it in text resources and `project.godot`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Variant {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Variant>),
+    /// Keys in the order they were written
+    Dictionary(Vec<(Variant, Variant)>),
+    /// `Vector2( 1, 2 )`, `PoolStringArray( "a", "b" )`, `ExtResource( 1 )` and
+    /// every other type written as a constructor
+    Constructor { name: String, args: Vec<Variant> },
+    /// `Object(InputEventKey,"scancode":65)`, used for input events
+    Object { class: String, properties: Vec<(String, Variant)> },
+}
+
+impl Variant {
+    pub fn parse(text: &str) -> Result<Variant> {
+        let mut parser = Parser { text, pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.pos == text.len() {
+            true => Ok(value),
+            false => Err(parser.error("unexpected text after value")),
+        }
+    }
+
+    /// The value of `key` in a dictionary with string keys
+    pub fn get(&self, key: &str) -> Option<&Variant> {
+        match self {
+            Variant::Dictionary(entries) => entries.iter().find(|(k, _)| k.as_str() == Some(key)).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Parse the value at the start of `text`, returns it
+    /// with the length of the text it was parsed from
+    pub fn parse_prefix(text: &str) -> Result<(Variant, usize)> {
+        let mut parser = Parser { text, pos: 0 };
+        let value = parser.value()?;
+        Ok((value, parser.pos))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Variant::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_variant(f, self, false)
+    }
+}
+
+/// Written the way Godot 3 writes it, reals inside constructors
+/// don't get a `.0` suffix
+fn write_variant(f: &mut fmt::Formatter, value: &Variant, in_constructor: bool) -> fmt::Result {
+    match value {
+        Variant::Nil => write!(f, "null"),
+        Variant::Bool(b) => write!(f, "{}", b),
+        Variant::Int(i) => write!(f, "{}", i),
+        Variant::Float(n) if n.is_nan() => write!(f, "nan"),
+        Variant::Float(n) if n.is_infinite() => write!(f, "{}", if *n > 0.0 { "inf" } else { "-inf" }),
+        Variant::Float(n) if n.fract() == 0.0 && !in_constructor => write!(f, "{:.1}", n),
+        Variant::Float(n) => write!(f, "{}", n),
+        Variant::String(s) => write_string(f, s),
+        Variant::Array(items) => {
+            write!(f, "[ ")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_variant(f, item, false)?;
+            }
+            write!(f, " ]")
+        }
+        Variant::Dictionary(entries) => {
+            writeln!(f, "{{")?;
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    writeln!(f, ",")?;
+                }
+                write_variant(f, key, false)?;
+                write!(f, ": ")?;
+                write_variant(f, value, false)?;
+            }
+            write!(f, "\n}}")
+        }
+        Variant::Constructor { name, args } => {
+            write!(f, "{}( ", name)?;
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_variant(f, arg, true)?;
+            }
+            write!(f, " )")
+        }
+        Variant::Object { class, properties } => {
+            write!(f, "Object({}", class)?;
+            for (key, value) in properties {
+                write!(f, ",")?;
+                write_string(f, key)?;
+                write!(f, ":")?;
+                write_variant(f, value, false)?;
+            }
+            // Godot ends objects with a line break, even inside arrays
+            writeln!(f, ")")
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        Error::Parse(format!("{} at offset {}", message, self.pos))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skip whitespace and consume `c` if it's next
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        match self.peek() == Some(c) {
+            true => {
+                self.pos += c.len_utf8();
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected '{}'", c))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Variant> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => Ok(Variant::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.dictionary(),
+            Some(c) if c == '-' || c == '+' || c == '.' || c.is_ascii_digit() => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.identifier();
+                match name.as_str() {
+                    "null" | "nil" => Ok(Variant::Nil),
+                    "true" => Ok(Variant::Bool(true)),
+                    "false" => Ok(Variant::Bool(false)),
+                    "inf" => Ok(Variant::Float(std::f64::INFINITY)),
+                    "nan" => Ok(Variant::Float(std::f64::NAN)),
+                    "Object" => self.object(),
+                    _ => self.constructor(name),
+                }
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len].to_string()
+    }
+
+    fn number(&mut self) -> Result<Variant> {
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| {
+                let sign_allowed = i == 0 || rest[..i].ends_with('e') || rest[..i].ends_with('E');
+                !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || ((c == '-' || c == '+') && sign_allowed))
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        let number = &rest[..len];
+
+        // `-inf`
+        if (number == "-" || number == "+") && rest[len..].starts_with("inf") {
+            self.pos += len + 3;
+            return Ok(Variant::Float(if number == "-" { std::f64::NEG_INFINITY } else { std::f64::INFINITY }));
+        }
+
+        let value = match number.contains(|c| c == '.' || c == 'e' || c == 'E') {
+            true => number.parse::<f64>().map(Variant::Float).ok(),
+            false => number.parse::<i64>().map(Variant::Int).ok(),
+        };
+        match value {
+            Some(v) => {
+                self.pos += len;
+                Ok(v)
+            }
+            None => Err(self.error(&format!("invalid number {}", number))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        let mut chars = self.rest().char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(s);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 'b')) => s.push('\u{8}'),
+                    Some((_, 'f')) => s.push('\u{c}'),
+                    Some((_, 'u')) => {
+                        let hex = chars.by_ref().take(4).map(|(_, c)| c).collect::<String>();
+                        match u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32) {
+                            Some(c) => s.push(c),
+                            None => return Err(self.error(&format!("invalid unicode escape \\u{}", hex))),
+                        }
+                    }
+                    Some((_, c)) => s.push(c),
+                    None => break,
+                },
+                c => s.push(c),
+            }
+        }
+
+        Err(self.error("unterminated string"))
+    }
+
+    fn array(&mut self) -> Result<Variant> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        while !self.eat(']') {
+            if !items.is_empty() {
+                self.expect(',')?;
+                // Trailing comma
+                if self.eat(']') {
+                    break;
+                }
+            }
+            items.push(self.value()?);
+        }
+        Ok(Variant::Array(items))
+    }
+
+    fn dictionary(&mut self) -> Result<Variant> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        while !self.eat('}') {
+            if !entries.is_empty() {
+                self.expect(',')?;
+                if self.eat('}') {
+                    break;
+                }
+            }
+            let key = self.value()?;
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+        }
+        Ok(Variant::Dictionary(entries))
+    }
+
+    fn constructor(&mut self, name: String) -> Result<Variant> {
+        self.expect('(')?;
+        let mut args = Vec::new();
+        while !self.eat(')') {
+            if !args.is_empty() {
+                self.expect(',')?;
+            }
+            args.push(self.value()?);
+        }
+        Ok(Variant::Constructor { name, args })
+    }
+
+    fn object(&mut self) -> Result<Variant> {
+        self.expect('(')?;
+        self.skip_whitespace();
+        let class = self.identifier();
+        if class.is_empty() {
+            return Err(self.error("expected a class name"));
+        }
+
+        let mut properties = Vec::new();
+        while !self.eat(')') {
+            self.expect(',')?;
+            let key = self.string()?;
+            self.expect(':')?;
+            properties.push((key, self.value()?));
+        }
+        Ok(Variant::Object { class, properties })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Variant {
+        Variant::String(s.to_string())
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(Variant::parse("null").unwrap(), Variant::Nil);
+        assert_eq!(Variant::parse("true").unwrap(), Variant::Bool(true));
+        assert_eq!(Variant::parse("-12").unwrap(), Variant::Int(-12));
+        assert_eq!(Variant::parse("0.5").unwrap(), Variant::Float(0.5));
+        assert_eq!(Variant::parse("1e-05").unwrap(), Variant::Float(0.00001));
+        assert_eq!(Variant::parse("-inf").unwrap(), Variant::Float(std::f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn nested_arrays_and_dictionaries() {
+        let value = Variant::parse("[ 1, [ 2, [  ] ], {\n\"a\": [ true, null ],\n\"b\": {\n\"c\": \"d\"\n}\n} ]").unwrap();
+        let expected = Variant::Array(vec![
+            Variant::Int(1),
+            Variant::Array(vec![Variant::Int(2), Variant::Array(vec![])]),
+            Variant::Dictionary(vec![
+                (string("a"), Variant::Array(vec![Variant::Bool(true), Variant::Nil])),
+                (string("b"), Variant::Dictionary(vec![(string("c"), string("d"))])),
+            ]),
+        ]);
+        assert_eq!(value, expected);
+        assert_eq!(
+            value.to_string(),
+            "[ 1, [ 2, [  ] ], {\n\"a\": [ true, null ],\n\"b\": {\n\"c\": \"d\"\n}\n} ]"
+        );
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let value = Variant::parse("[ 1, 2, ]").unwrap();
+        assert_eq!(value, Variant::Array(vec![Variant::Int(1), Variant::Int(2)]));
+        let value = Variant::parse("{ \"a\": 1, }").unwrap();
+        assert_eq!(value, Variant::Dictionary(vec![(string("a"), Variant::Int(1))]));
+    }
+
+    #[test]
+    fn constructors() {
+        let value = Variant::parse("Vector2( 1, 2.5 )").unwrap();
+        let expected = Variant::Constructor {
+            name: "Vector2".to_string(),
+            args: vec![Variant::Int(1), Variant::Float(2.5)],
+        };
+        assert_eq!(value, expected);
+        assert_eq!(value.to_string(), "Vector2( 1, 2.5 )");
+
+        let value = Variant::parse("PoolStringArray( \"a\", \"b\" )").unwrap();
+        assert_eq!(value.to_string(), "PoolStringArray( \"a\", \"b\" )");
+
+        // Reals inside constructors are written without a fraction
+        let value = Variant::Constructor {
+            name: "Color".to_string(),
+            args: vec![Variant::Float(1.0), Variant::Float(0.5), Variant::Float(0.0), Variant::Float(1.0)],
+        };
+        assert_eq!(value.to_string(), "Color( 1, 0.5, 0, 1 )");
+        assert_eq!(Variant::Float(1.0).to_string(), "1.0");
+    }
+
+    #[test]
+    fn input_event_objects() {
+        let text = "Object(InputEventKey,\"resource_local_to_scene\":false,\"device\":0,\"alt\":false,\"scancode\":32,\"script\":null)\n";
+        let value = Variant::parse(text).unwrap();
+        match &value {
+            Variant::Object { class, properties } => {
+                assert_eq!(class, "InputEventKey");
+                let keys = properties.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>();
+                assert_eq!(keys, vec!["resource_local_to_scene", "device", "alt", "scancode", "script"]);
+                assert_eq!(properties[3].1, Variant::Int(32));
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+        assert_eq!(value.to_string(), text);
+    }
+
+    #[test]
+    fn objects_inside_an_action() {
+        let text = "{\n\"deadzone\": 0.5,\n\"events\": [ Object(InputEventKey,\"scancode\":65)\n, Object(InputEventJoypadButton,\"button_index\":0)\n ]\n}";
+        let value = Variant::parse(text).unwrap();
+        assert_eq!(value.get("deadzone"), Some(&Variant::Float(0.5)));
+        assert_eq!(value.to_string(), text);
+    }
+
+    #[test]
+    fn escaped_strings() {
+        let value = Variant::parse(r#""say \"hi\" \\ C:\\path\n\ttab \u00e9""#).unwrap();
+        assert_eq!(value, string("say \"hi\" \\ C:\\path\n\ttab \u{e9}"));
+
+        // Godot only escapes quotes and backslashes
+        assert_eq!(string("a \"b\" \\ c\nd").to_string(), "\"a \\\"b\\\" \\\\ c\nd\"");
+        assert_eq!(Variant::parse(&string("a \"b\" \\ c").to_string()).unwrap(), string("a \"b\" \\ c"));
+    }
+
+    #[test]
+    fn parse_prefix_stops_after_the_value() {
+        let (value, len) = Variant::parse_prefix("\"a\" ; comment").unwrap();
+        assert_eq!(value, string("a"));
+        assert_eq!(len, 3);
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!(Variant::parse("\"unterminated").is_err());
+        assert!(Variant::parse("[ 1, 2").is_err());
+        assert!(Variant::parse("1 2").is_err());
+        assert!(Variant::parse("Object(,\"a\":1)").is_err());
+        assert!(Variant::parse("\"\\uZZZZ\"").is_err());
+    }
+}
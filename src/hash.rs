@@ -0,0 +1,16 @@
+use std::fs::read;
+use std::path::Path;
+
+use data_encoding::HEXLOWER;
+use ring::digest::{digest, SHA256};
+
+use crate::errors::Result;
+
+/// Hex encoded SHA-256 of `data`
+pub fn sha256(data: &[u8]) -> String {
+    HEXLOWER.encode(digest(&SHA256, data).as_ref())
+}
+
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    Ok(sha256(&read(path)?))
+}
@@ -0,0 +1,94 @@
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_file, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use data_encoding::BASE64;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::config::config_dir;
+use crate::errors::Result;
+
+const KEY_EXT: &'static str = "pub";
+
+lazy_static! {
+    static ref KEY_NAME: Regex = Regex::new(r"^[a-zA-Z0-9]+[a-zA-Z0-9\-_]*$").unwrap();
+}
+
+/// The trusted keys store, one base64 encoded Ed25519 public key
+/// per file in `~/.config/gdt/keys/<name>.pub`
+pub fn keys_dir() -> PathBuf {
+    config_dir().join("keys")
+}
+
+/// All trusted keys as (name, public key) pairs
+pub fn trusted_keys() -> Vec<(String, Vec<u8>)> {
+    let entries = match read_dir(keys_dir()) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut keys = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|ext| ext == KEY_EXT).unwrap_or(false))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            let key = BASE64.decode(read_to_string(&path).ok()?.trim().as_bytes()).ok()?;
+            Some((name, key))
+        })
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+/// Name of the trusted key matching `public_key`, if any
+pub fn find_trusted(public_key: &[u8]) -> Option<String> {
+    trusted_keys()
+        .into_iter()
+        .find(|(_, key)| key.as_slice() == public_key)
+        .map(|(name, _)| name)
+}
+
+pub fn add(name: String, key: String) {
+    if !KEY_NAME.is_match(&name) {
+        eprintln!("Invalid key name \"{}\" (only alphanmeric characters, '-' and '_')", name);
+        return;
+    }
+
+    match BASE64.decode(key.trim().as_bytes()) {
+        Ok(ref bytes) if bytes.len() == 32 => {}
+        _ => {
+            eprintln!("Invalid key, expected a base64 encoded Ed25519 public key");
+            return;
+        }
+    }
+
+    if let Err(e) = write_key(&name, key.trim()) {
+        eprintln!("Failed to add key: {}", e);
+        return;
+    }
+    println!("Added key: {}", name);
+}
+
+fn write_key(name: &str, key: &str) -> Result<()> {
+    create_dir_all(keys_dir())?;
+    let mut file = File::create(keys_dir().join(format!("{}.{}", name, KEY_EXT)))?;
+    file.write_all(key.as_bytes())?;
+    file.write_all(b"\n")?;
+    Ok(())
+}
+
+pub fn list() {
+    for (name, key) in trusted_keys() {
+        println!("{} {}", name, BASE64.encode(&key));
+    }
+}
+
+pub fn remove(name: String) {
+    let path = keys_dir().join(format!("{}.{}", name, KEY_EXT));
+    match remove_file(&path) {
+        Ok(_) => println!("Removed key: {}", name),
+        Err(e) => eprintln!("Failed to remove key {}: {}", name, e),
+    }
+}
@@ -6,6 +6,13 @@ mod requirements;
 mod package;
 mod errors;
 mod godot;
+mod config;
+mod credentials;
+mod remote;
+mod hash;
+mod keys;
+mod diff;
+mod project;
 
 
 #[derive(StructOpt, Debug)]
@@ -18,26 +25,227 @@ enum Opts {
 
         #[structopt(name = "path", help = "path to a single package")]
         path: Option<String>,
+
+        #[structopt(long, help = "allow plain http package sources")]
+        allow_insecure: bool,
+
+        #[structopt(long, help = "refuse packages with inspection findings at or above this level (low, medium, high)")]
+        deny: Option<package::Severity>,
+
+        #[structopt(long, number_of_values = 1, help = "optional group of files to install, e.g. examples (repeatable)")]
+        with: Vec<String>,
+    },
+    #[structopt(about = "Update installed packages, all of them if no package is given")]
+    Update {
+        #[structopt(short, long, help = "file containing list of packages")]
+        requirements: Option<PathBuf>,
+
+        #[structopt(name = "path", help = "name of an installed package or path to a single package")]
+        path: Option<String>,
+
+        #[structopt(short, long, help = "overwrite locally modified files instead of merging")]
+        force: bool,
+
+        #[structopt(long, help = "allow plain http package sources")]
+        allow_insecure: bool,
+
+        #[structopt(long, help = "refuse packages with inspection findings at or above this level (low, medium, high)")]
+        deny: Option<package::Severity>,
+
+        #[structopt(long, number_of_values = 1, help = "optional group of files to install, e.g. examples (repeatable)")]
+        with: Vec<String>,
+    },
+    #[structopt(about = "Remove an installed package")]
+    Uninstall {
+        #[structopt(name = "name", help = "name, or author/name, of the installed package")]
+        name: String,
+
+        #[structopt(short, long, help = "uninstall even if the project's scenes or scripts still use the package")]
+        force: bool,
     },
+    #[structopt(about = "Show which package owns a file, or list unowned and conflicting files")]
+    Owns {
+        #[structopt(name = "path", help = "file in the project")]
+        path: Option<String>,
+    },
+    #[structopt(about = "Show locally modified package files")]
+    Status,
+    #[structopt(about = "Show what changes between an installed package and an available version")]
+    Diff {
+        #[structopt(name = "name", help = "name of the installed package")]
+        name: String,
+
+        #[structopt(long, help = "package source to compare with, defaults to where it was installed from")]
+        to: Option<String>,
+    },
+    #[structopt(about = "List installed packages with a newer version available")]
+    Outdated,
+    #[structopt(about = "Scan a package for risky API usage")]
+    Inspect {
+        #[structopt(name = "source", help = "path or url of the package")]
+        source: String,
+
+        #[structopt(long, help = "exit with an error on findings at or above this level (low, medium, high)")]
+        deny: Option<package::Severity>,
+    },
+    #[structopt(about = "List everything a scene or resource needs, directly or through other files")]
+    Deps {
+        #[structopt(name = "file", help = "res:// path or path in the project")]
+        file: String,
+    },
+    #[structopt(about = "List everything that uses a file, directly or through other files")]
+    Rdeps {
+        #[structopt(name = "file", help = "res:// path or path in the project")]
+        file: String,
+    },
+    #[structopt(about = "Copy a scene and everything it needs into a new package")]
+    Extract {
+        #[structopt(name = "scene", help = "res:// path or path in the project of the scene")]
+        scene: String,
+
+        #[structopt(long, help = "package directory to create")]
+        to: PathBuf,
+
+        #[structopt(long, help = "package name, defaults to the name of the scene")]
+        name: Option<String>,
+
+        #[structopt(long, help = "package author, needed by projects using the namespaced layout")]
+        author: Option<String>,
+    },
+    #[structopt(about = "Move or rename a file or directory of the project and update the references to it")]
+    Mv {
+        #[structopt(name = "from", help = "res:// path or path in the project")]
+        from: String,
+
+        #[structopt(name = "to", help = "res:// path or path in the project")]
+        to: String,
+
+        #[structopt(long, help = "list the moves and edits without making them")]
+        dry_run: bool,
+    },
+    #[structopt(about = "Move packages to pack/<author>/<name> and switch the project to the namespaced layout")]
+    MigrateLayout,
     #[structopt(about = "Verify a manifest file")]
     Verify {
         #[structopt(short, long, help = "path to manifest file")]
         manifest: PathBuf,
     },
+    #[structopt(about = "Create or update package manifests")]
+    Manifest(ManifestOpts),
     #[structopt(about = "Init a Godot project")]
     Init {
         #[structopt(name = "project name", help = "godot project name")]
         name: String,
-    }
+    },
+    #[structopt(about = "Sign a package")]
+    Sign {
+        #[structopt(name = "dir", help = "package directory containing a manifest")]
+        dir: PathBuf,
+
+        #[structopt(short, long, help = "pkcs8 signing key (defaults to ~/.config/gdt/signing_key.pk8)")]
+        key: Option<PathBuf>,
+    },
+    #[structopt(about = "Manage trusted package signing keys")]
+    Keys(KeysOpts),
+    #[structopt(about = "Read and change the settings in project.godot")]
+    Config(ConfigOpts),
 }
 
+#[derive(StructOpt, Debug)]
+enum KeysOpts {
+    #[structopt(about = "Trust a public key")]
+    Add {
+        #[structopt(name = "name", help = "name of the key")]
+        name: String,
+
+        #[structopt(name = "key", help = "base64 encoded Ed25519 public key")]
+        key: String,
+    },
+    #[structopt(about = "List trusted keys")]
+    List,
+    #[structopt(about = "Remove a trusted key")]
+    Remove {
+        #[structopt(name = "name", help = "name of the key")]
+        name: String,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum ManifestOpts {
+    #[structopt(about = "Write a manifest listing the files of a package directory, asking for any field not given")]
+    Init {
+        #[structopt(name = "dir", help = "package directory")]
+        dir: PathBuf,
+
+        #[structopt(long, help = "package name")]
+        name: Option<String>,
+
+        #[structopt(long, help = "package author")]
+        author: Option<String>,
+
+        #[structopt(long, help = "package version, e.g. 0.1")]
+        version: Option<String>,
+
+        #[structopt(long, help = "short description of the package")]
+        description: Option<String>,
+
+        #[structopt(long, help = "how to use the package")]
+        usage: Option<String>,
+
+        #[structopt(long, help = "refresh the files of an existing manifest instead")]
+        update: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum ConfigOpts {
+    #[structopt(about = "Print a setting")]
+    Get {
+        #[structopt(name = "setting", help = "section/key, e.g. application/config/name")]
+        setting: String,
+    },
+    #[structopt(about = "Change a setting")]
+    Set {
+        #[structopt(name = "setting", help = "section/key, e.g. application/config/name")]
+        setting: String,
+
+        #[structopt(name = "value", help = "Godot value, e.g. true, 1.5, Vector2( 1, 2 ), plain text is set as a string")]
+        value: String,
+    },
+}
 
 fn main() {
     pretty_env_logger::init();
     let opt = Opts::from_args();
     match opt {
-        Opts::Install { requirements, path } => package::install_packages(requirements, path, false),
+        Opts::Install { requirements, path, allow_insecure, deny, with } => {
+            package::install_packages(requirements, path, false, package::InstallOptions { allow_insecure, force: false, deny, with })
+        }
+        Opts::Update { requirements, path, force, allow_insecure, deny, with } => {
+            package::install_packages(requirements, path, true, package::InstallOptions { allow_insecure, force, deny, with })
+        }
+        Opts::Uninstall { name, force } => package::uninstall(name, force),
+        Opts::Owns { path } => package::owns(path),
+        Opts::Status => package::status(),
+        Opts::Diff { name, to } => package::diff(name, to),
+        Opts::Outdated => package::outdated(),
+        Opts::Inspect { source, deny } => package::inspect(source, deny),
+        Opts::Deps { file } => project::deps(file),
+        Opts::Rdeps { file } => project::rdeps(file),
+        Opts::Mv { from, to, dry_run } => project::mv(from, to, dry_run),
+        Opts::Extract { scene, to, name, author } => package::extract(scene, to, name, author),
+        Opts::MigrateLayout => package::migrate_layout(),
         Opts::Verify { manifest } => package::verify(manifest),
+        Opts::Manifest(ManifestOpts::Init { dir, update: true, .. }) => package::update_manifest(dir),
+        Opts::Manifest(ManifestOpts::Init { dir, name, author, version, description, usage, update: false }) => {
+            package::init_manifest(dir, package::ManifestFields { name, author, version, description, usage })
+        }
         Opts::Init { name } => godot::init(name),
+        Opts::Sign { dir, key } => package::sign(dir, key),
+        Opts::Keys(KeysOpts::Add { name, key }) => keys::add(name, key),
+        Opts::Keys(KeysOpts::List) => keys::list(),
+        Opts::Keys(KeysOpts::Remove { name }) => keys::remove(name),
+        Opts::Config(ConfigOpts::Get { setting }) => godot::config_get(setting),
+        Opts::Config(ConfigOpts::Set { setting, value }) => godot::config_set(setting, value),
     }
 }
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{metadata, read};
+use std::path::Path;
+
+use crate::diff;
+use crate::hash::sha256;
+
+use super::package::Package;
+
+/// Files shown as a unified diff, everything else is compared by size and hash
+const TEXT_EXTENSIONS: [&'static str; 4] = ["gd", "tscn", "tres", "cfg"];
+
+/// Print the differences between an installed package and a candidate
+/// version fetched to `candidate_dir`, returns true if there are any
+pub fn compare(installed_dir: &Path, installed: &Package, candidate_dir: &Path, candidate: &Package) -> bool {
+    let old_files = installed
+        .files
+        .iter()
+        .map(|file| (file.src(), file.target(installed_dir)))
+        .collect::<BTreeMap<_, _>>();
+    let new_files = candidate.files.iter().map(|file| file.src()).collect::<BTreeSet<_>>();
+    let mut changed = false;
+
+    if installed.version() != candidate.version() {
+        println!("version: {} -> {}", installed.version(), candidate.version());
+        changed = true;
+    }
+
+    for file in new_files.iter().filter(|file| !old_files.contains_key(*file)) {
+        println!("added:   {}", file);
+        changed = true;
+    }
+    for file in old_files.keys().filter(|file| !new_files.contains(*file)) {
+        println!("removed: {}", file);
+        changed = true;
+    }
+
+    for (file, old) in old_files.iter().filter(|(file, _)| new_files.contains(*file)) {
+        changed |= compare_file(file, old, &candidate_dir.join(file));
+    }
+
+    changed
+}
+
+fn compare_file(file: &str, old: &Path, new: &Path) -> bool {
+    let (old_data, new_data) = match (read(old), read(new)) {
+        (Ok(o), Ok(n)) => (o, n),
+        (Err(_), Ok(_)) => {
+            println!("missing: {} (deleted locally)", file);
+            return true;
+        }
+        _ => return false,
+    };
+
+    if old_data == new_data {
+        return false;
+    }
+
+    let is_text = Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| TEXT_EXTENSIONS.contains(&ext))
+        .unwrap_or(false);
+
+    match (is_text, std::str::from_utf8(&old_data), std::str::from_utf8(&new_data)) {
+        (true, Ok(old_text), Ok(new_text)) => {
+            let a = format!("a/{}", file);
+            let b = format!("b/{}", file);
+            print!("{}", diff::unified(&a, &b, old_text, new_text, 3));
+        }
+        _ => {
+            println!(
+                "changed: {} ({} -> {} bytes, {} -> {})",
+                file,
+                size(old),
+                size(new),
+                &sha256(&old_data)[..12],
+                &sha256(&new_data)[..12]
+            );
+        }
+    }
+
+    true
+}
+
+fn size(path: &Path) -> u64 {
+    metadata(path).map(|m| m.len()).unwrap_or(0)
+}
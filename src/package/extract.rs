@@ -0,0 +1,236 @@
+use std::collections::BTreeSet;
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir, remove_dir_all, remove_file, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+use crate::config;
+use crate::errors::Result;
+use crate::godot::{ConfigFile, PROJECT_FILE};
+use crate::project::{relative_res_path, res_file, res_path, to_res_path, DependencyGraph, REFERENCING_EXTENSIONS, TEXT_RESOURCE_EXTENSIONS};
+
+use super::install_path;
+use super::manifest::{self, ManifestFields};
+use super::package::is_valid_name;
+use super::project_settings::AUTOLOAD;
+
+lazy_static! {
+    static ref RES_STRING: Regex = Regex::new(r#""(res://[^"]+)""#).unwrap();
+    /// The `path` of an `ext_resource` heading
+    static ref EXT_RESOURCE_PATH: Regex = Regex::new(r#"(?m)^(\[ext_resource\b[^\n]*?\bpath=)"(res://[^"]+)""#).unwrap();
+    static ref PRELOAD: Regex = Regex::new(r#"\b(preload\s*\(\s*)"(res://[^"]+)""#).unwrap();
+}
+
+/// Copy a scene and every project file it needs, directly or through other
+/// files, into the package directory `dir` and write a manifest for it.
+///
+/// Files keep their layout below the directory they have in common. The
+/// `ext_resource`s and `preload`s between them are made relative, other
+/// references point to where the package is installed in this project's
+/// layout. What can't be included is listed
+pub fn extract(scene: String, dir: PathBuf, name: Option<String>, author: Option<String>) {
+    let scene = to_res_path(&scene);
+    if !res_file(&scene).is_file() {
+        eprintln!("{} does not exist", scene);
+        return;
+    }
+    if read_dir(&dir).map(|mut entries| entries.next().is_some()).unwrap_or(false) {
+        eprintln!("{} is not empty", dir.display());
+        return;
+    }
+
+    let name = name.unwrap_or_else(|| package_name(&scene));
+    if !is_valid_name(&name) {
+        eprintln!("Invalid name \"{}\" (only alphanmeric characters, '-' and '_'), use --name", name);
+        return;
+    }
+    if let Some(author) = author.as_ref().filter(|author| !is_valid_name(author)) {
+        eprintln!("Invalid author \"{}\" (only alphanmeric characters, '-' and '_')", author);
+        return;
+    }
+    let installed = match install_path(&name, author.as_ref().map(|a| a.as_str()), config::load_project().namespaced) {
+        Some(dst) => format!("{}/", res_path(&dst)),
+        None => {
+            eprintln!("The namespaced layout requires an author, use --author");
+            return;
+        }
+    };
+
+    let mut needed = DependencyGraph::build(Path::new(".")).dependencies(&scene);
+    needed.insert(scene.clone());
+
+    let mut left_out = Vec::new();
+    let mut files = BTreeSet::new();
+    for path in needed {
+        if !path.starts_with("res://") {
+            left_out.push(format!("{} (not a project file)", path));
+        } else if !res_file(&path).is_file() {
+            left_out.push(format!("{} (missing)", path));
+        } else {
+            files.insert(path);
+        }
+    }
+
+    let root = common_dir(&files);
+    let extracted = Extracted {
+        files: &files,
+        root: &root,
+        installed: &installed,
+    };
+    let created = !dir.exists();
+    let mut copied = Vec::new();
+    for file in &files {
+        let target = dir.join(&file[root.len()..]);
+        copied.push(target.clone());
+        if let Err(e) = extracted.copy(file, &target) {
+            eprintln!("Failed to copy {} to {}: {}", file, target.display(), e);
+            remove_copied(&dir, &copied, created);
+            return;
+        }
+    }
+    println!("Extracted {} files to {}", files.len(), dir.display());
+
+    left_out.extend(autoloads_used(&files));
+    if !left_out.is_empty() {
+        println!("Not included:");
+        for item in &left_out {
+            println!("  {}", item);
+        }
+    }
+
+    let fields = ManifestFields {
+        name: Some(name),
+        author,
+        version: None,
+        description: None,
+        usage: None,
+    };
+    manifest::init(dir, fields);
+}
+
+/// The extracted files, `res://` paths below `root`, and the `res://`
+/// directory the package is installed to
+struct Extracted<'a> {
+    files: &'a BTreeSet<String>,
+    root: &'a str,
+    installed: &'a str,
+}
+
+impl<'a> Extracted<'a> {
+    /// Copy `file` to `target`, rewriting its references to the other
+    /// extracted files. `ext_resource` paths and `preload` arguments, which
+    /// Godot resolves relative to the file, are made relative, any other
+    /// reference is made to point to the installed file
+    fn copy(&self, file: &str, target: &Path) -> Result<()> {
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let source = res_file(file);
+        let extension = source.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if !REFERENCING_EXTENSIONS.contains(&extension) {
+            copy(&source, target)?;
+            return Ok(());
+        }
+
+        let relative = match extension {
+            "gd" => Some(&*PRELOAD),
+            ext if TEXT_RESOURCE_EXTENSIONS.contains(&ext) => Some(&*EXT_RESOURCE_PATH),
+            _ => None,
+        };
+
+        let mut text = read_to_string(&source)?;
+        if let Some(pattern) = relative {
+            text = pattern
+                .replace_all(&text, |caps: &Captures| match self.files.contains(&caps[2]) {
+                    true => format!("{}\"{}\"", &caps[1], relative_res_path(file, &caps[2])),
+                    false => caps[0].to_string(),
+                })
+                .to_string();
+        }
+        let rewritten = RES_STRING.replace_all(&text, |caps: &Captures| match self.files.contains(&caps[1]) {
+            true => format!("\"{}{}\"", self.installed, &caps[1][self.root.len()..]),
+            false => caps[0].to_string(),
+        });
+
+        File::create(target)?.write_all(rewritten.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Undo a failed extract: remove the `copied` files and the directories they
+/// leave empty in `dir`, or `dir` itself if the extract `created` it
+fn remove_copied(dir: &Path, copied: &[PathBuf], created: bool) {
+    if created {
+        if let Err(e) = remove_dir_all(dir) {
+            eprintln!("Failed to remove {}: {}", dir.display(), e);
+        }
+        return;
+    }
+
+    for file in copied {
+        let _ = remove_file(file);
+        let mut parent = file.parent();
+        while let Some(d) = parent {
+            if d == dir || remove_dir(d).is_err() {
+                break;
+            }
+            parent = d.parent();
+        }
+    }
+}
+
+/// The deepest directory containing all of `files`, as a `res://` path ending with `/`
+fn common_dir(files: &BTreeSet<String>) -> String {
+    let mut common: Option<Vec<&str>> = None;
+    for file in files {
+        let mut dir = file.trim_start_matches("res://").split('/').collect::<Vec<_>>();
+        dir.pop();
+        common = Some(match common {
+            None => dir,
+            Some(common) => common.into_iter().zip(dir).take_while(|(a, b)| a == b).map(|(a, _)| a).collect(),
+        });
+    }
+
+    let dirs = common.unwrap_or_default().iter().map(|dir| format!("{}/", dir)).collect::<String>();
+    format!("res://{}", dirs)
+}
+
+/// The project's autoloads referred to by name in the extracted scripts
+fn autoloads_used(files: &BTreeSet<String>) -> Vec<String> {
+    let project = match ConfigFile::load(Path::new(PROJECT_FILE)) {
+        Ok(p) => p,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut used = Vec::new();
+    for name in project.keys(AUTOLOAD) {
+        let pattern = Regex::new(&format!(r"\b{}\b", regex::escape(name))).unwrap();
+        let users = files
+            .iter()
+            .filter(|file| file.ends_with(".gd"))
+            .filter(|file| read_to_string(res_file(file)).map(|text| pattern.is_match(&text)).unwrap_or(false))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !users.is_empty() {
+            used.push(format!("autoload {}, used by {}", name, users.join(", ")));
+        }
+    }
+    used
+}
+
+/// A valid package name from the file name of the scene
+fn package_name(scene: &str) -> String {
+    let stem = Path::new(scene).file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let name = stem
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            true => c,
+            false => '-',
+        })
+        .collect::<String>();
+    name.trim_start_matches(|c| c == '-' || c == '_').to_string()
+}
@@ -0,0 +1,118 @@
+use std::fs::read_dir;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::errors::{Error, Result};
+
+use super::package::MANIFEST;
+use super::record::{project_path, META_DIR};
+use super::signing::{Signature, SIGNATURE};
+
+/// Expand the paths and patterns of a group into the files they match.
+///
+/// Patterns need the files of the package: a local package directory is
+/// listed, a remote package only has the files in its signature
+pub fn expand(patterns: &[String], available: Option<&[String]>) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+
+    for pattern in patterns {
+        if !is_pattern(pattern) {
+            files.push(pattern.clone());
+            continue;
+        }
+
+        let available = match available {
+            Some(a) => a,
+            None => return Err(Error::UnlistedPattern(pattern.clone())),
+        };
+
+        let regex = pattern_regex(pattern);
+        files.extend(available.iter().filter(|file| regex.is_match(file)).cloned());
+    }
+
+    let mut unique = Vec::new();
+    for file in files {
+        if !unique.contains(&file) {
+            unique.push(file);
+        }
+    }
+    Ok(unique)
+}
+
+/// Every file in a local package directory, skipping the manifest,
+/// the signature and hidden files
+pub fn local_files(dir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    walk(dir, dir, &mut files);
+    files.sort();
+    files
+}
+
+/// Every file covered by a signature
+pub fn signed_files(signature: &Signature) -> Vec<String> {
+    signature.files.keys().cloned().collect()
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) {
+    let entries = match read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name == META_DIR {
+            continue;
+        }
+
+        let path = entry.path();
+        if path.is_dir() {
+            walk(root, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            if relative != Path::new(MANIFEST) && relative != Path::new(SIGNATURE) {
+                files.push(project_path(relative));
+            }
+        }
+    }
+}
+
+/// `path` is `pattern` or matches it
+pub fn matches(pattern: &str, path: &str) -> bool {
+    match is_pattern(pattern) {
+        true => pattern_regex(pattern).is_match(path),
+        false => pattern == path,
+    }
+}
+
+fn is_pattern(path: &str) -> bool {
+    path.contains('*') || path.contains('?')
+}
+
+/// `*` and `?` match within a path segment, `**` matches any number of segments
+fn pattern_regex(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all
+                match chars.peek() {
+                    Some('/') => {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    }
+                    _ => regex.push_str(".*"),
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::errors::Result;
+
+use super::package::Package;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            _ => Err(format!("invalid level \"{}\" (expected low, medium or high)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Low => write!(f, "low"),
+            Self::Medium => write!(f, "medium"),
+            Self::High => write!(f, "high"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub file: String,
+    pub line: usize,
+    pub severity: Severity,
+    pub description: &'static str,
+    pub code: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: [{}] {}: {}", self.file, self.line, self.severity, self.description, self.code)
+    }
+}
+
+struct Rule {
+    pattern: Regex,
+    severity: Severity,
+    description: &'static str,
+    /// Exempt a match, given the first argument of the call
+    allow: fn(&str) -> bool,
+}
+
+impl Rule {
+    fn new(pattern: &str, severity: Severity, description: &'static str, allow: fn(&str) -> bool) -> Self {
+        Self {
+            pattern: Regex::new(pattern).unwrap(),
+            severity,
+            description,
+            allow,
+        }
+    }
+}
+
+fn never(_: &str) -> bool {
+    false
+}
+
+fn in_user_dir(arg: &str) -> bool {
+    arg.starts_with("\"user://")
+}
+
+/// `remove` and friends are common on other types, only
+/// consider calls with a literal path
+fn not_a_path_or_in_user_dir(arg: &str) -> bool {
+    !arg.starts_with('"') || in_user_dir(arg)
+}
+
+/// A string literal inside the project, either `res://` or relative
+fn in_project(arg: &str) -> bool {
+    if arg.starts_with("\"res://") {
+        return true;
+    }
+    arg.starts_with('"') && !arg.contains("://") && !arg.starts_with("\"/") && !arg.contains(":/") && !arg.contains(":\\")
+}
+
+lazy_static! {
+    static ref RULES: Vec<Rule> = vec![
+        Rule::new(r"\bOS\.execute\s*\(", Severity::High, "runs an external program", never),
+        Rule::new(r"\bOS\.kill\s*\(", Severity::High, "kills a process", never),
+        Rule::new(r"\bJavaScript\.eval\s*\(", Severity::High, "evaluates javascript", never),
+        Rule::new(r"\bOS\.shell_open\s*\(", Severity::Medium, "opens a url or file with the os", never),
+        Rule::new(
+            r"\.open(?:_compressed|_encrypted|_encrypted_with_pass)?\s*\([^,]+,\s*File\.(?:WRITE|READ_WRITE|WRITE_READ)\b",
+            Severity::Medium,
+            "writes a file outside user://",
+            in_user_dir,
+        ),
+        Rule::new(
+            r"\.make_dir(?:_recursive)?\s*\(",
+            Severity::Medium,
+            "modifies the file system outside user://",
+            in_user_dir,
+        ),
+        Rule::new(
+            r"\.(?:remove|rename|copy)\s*\(",
+            Severity::Medium,
+            "modifies the file system outside user://",
+            not_a_path_or_in_user_dir,
+        ),
+        Rule::new(r"\bHTTP(?:Request|Client)\b", Severity::Medium, "makes network requests", never),
+        Rule::new(
+            r"\b(?:StreamPeerTCP|PacketPeerUDP|WebSocketClient)\b",
+            Severity::Medium,
+            "opens a network connection",
+            never,
+        ),
+        Rule::new(
+            r"\b(?:pre)?load\s*\(",
+            Severity::Low,
+            "loads a resource from outside the project or from a dynamic path",
+            in_project,
+        ),
+    ];
+
+    static ref EMBEDDED_SOURCE: Regex = Regex::new(r#"(?m)^script/source\s*=\s*""#).unwrap();
+}
+
+/// Scan the GDScript of a package, both `.gd` files and scripts embedded
+/// in scenes and resources. `dir` holds the files as they will be installed
+pub fn inspect(dir: &Path, package: &Package) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+
+    for file in package.files.iter().map(|file| file.src()) {
+        let is_script = file.ends_with(".gd");
+        if !is_script && !file.ends_with(".tscn") && !file.ends_with(".tres") {
+            continue;
+        }
+
+        let source = read_to_string(dir.join(file))?;
+
+        match is_script {
+            true => scan(file, &source, 0, &mut findings),
+            false => {
+                for (offset, script) in embedded_scripts(&source) {
+                    scan(file, &script, offset, &mut findings);
+                }
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Scan a script, `offset` is the line the script starts on in `file`
+fn scan(file: &str, source: &str, offset: usize, findings: &mut Vec<Finding>) {
+    for (i, line) in source.lines().enumerate() {
+        let code = strip_comment(line);
+
+        for rule in RULES.iter() {
+            if let Some(m) = rule.pattern.find(code) {
+                let rest = &code[m.start()..];
+                let arg = match rest.find('(') {
+                    Some(pos) => rest[pos + 1..].trim_start(),
+                    None => "",
+                };
+                if (rule.allow)(arg) {
+                    continue;
+                }
+
+                findings.push(Finding {
+                    file: file.to_string(),
+                    line: offset + i + 1,
+                    severity: rule.severity,
+                    description: rule.description,
+                    code: line.trim().to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// The line up to a `#` comment, a `#` inside a string doesn't start one
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (pos, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return &line[..pos],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// The `script/source` strings of built in scripts in a scene or resource,
+/// along with the line they start on
+fn embedded_scripts(scene: &str) -> Vec<(usize, String)> {
+    let mut scripts = Vec::new();
+
+    for m in EMBEDDED_SOURCE.find_iter(scene) {
+        let offset = scene[..m.start()].matches('\n').count();
+
+        let mut script = String::new();
+        let mut chars = scene[m.end()..].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => script.push('\n'),
+                    Some('t') => script.push('\t'),
+                    Some(c) => script.push(c),
+                    None => break,
+                },
+                c => script.push(c),
+            }
+        }
+        scripts.push((offset, script));
+    }
+
+    scripts
+}
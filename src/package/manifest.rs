@@ -0,0 +1,225 @@
+use std::fs::{read_to_string, File};
+use std::io::{stdin, stdout, Write};
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::errors::Result;
+
+use super::groups;
+use super::package::{Package, PackageFile, MANIFEST};
+
+const DEFAULT_VERSION: &'static str = "0.1";
+/// The files Godot uses: scripts, scenes, resources, shaders and the assets
+/// it imports. READMEs, licenses, CI configuration and the like are left out
+const PACKAGE_EXTENSIONS: [&'static str; 44] = [
+    // Scripts and native libraries
+    "gd", "gdns", "gdnlib", "cs", "vs", "so", "dll", "dylib", "wasm",
+    // Scenes, resources and plugin configuration
+    "tscn", "scn", "escn", "tres", "res", "cfg",
+    // Shaders
+    "shader", "gdshader",
+    // Images
+    "png", "jpg", "jpeg", "webp", "svg", "bmp", "tga", "hdr", "exr",
+    // Audio and video
+    "wav", "ogg", "mp3", "opus", "webm", "ogv",
+    // Fonts
+    "ttf", "otf", "woff", "woff2", "fnt",
+    // Models
+    "glb", "gltf", "obj", "dae",
+    // Translations and data
+    "csv", "po", "json",
+];
+
+lazy_static! {
+    /// The `files` array, up to the `]` that ends a line
+    static ref FILES: Regex = Regex::new(r"(?ms)^files\s*=\s*\[.*?\][ \t]*$").unwrap();
+}
+
+/// Manifest fields given on the command line, anything not given is asked for
+pub struct ManifestFields {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub usage: Option<String>,
+}
+
+/// Write a manifest listing every file in `dir`
+pub fn init(dir: PathBuf, fields: ManifestFields) {
+    let path = dir.join(MANIFEST);
+    if path.exists() {
+        eprintln!("{} already exists, use --update to refresh its files", path.display());
+        return;
+    }
+
+    let default_name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or_default();
+
+    let name = fields.name.unwrap_or_else(|| ask("Name", &default_name));
+    let author = fields.author.unwrap_or_else(|| ask("Author (optional)", ""));
+    let version = fields.version.unwrap_or_else(|| ask("Version", DEFAULT_VERSION));
+    let description = fields.description.unwrap_or_else(|| ask("Description", ""));
+    let usage = fields.usage.unwrap_or_else(|| ask("Usage", ""));
+
+    let files = package_files(&dir, &[]);
+    let mut text = format!("name = {}\n", quote(&name));
+    if !author.is_empty() {
+        text.push_str(&format!("author = {}\n", quote(&author)));
+    }
+    text.push_str(&format!("version = {}\n", quote(&version)));
+    text.push_str(&format!("description = {}\n", quote(&description)));
+    text.push_str(&format!("usage = {}\n\n", quote(&usage)));
+    text.push_str(&files_array(&files.iter().map(|f| PackageFile::Path(f.clone())).collect::<Vec<_>>()));
+    text.push('\n');
+
+    if !is_valid(&text) {
+        return;
+    }
+
+    match write_manifest(&path, &text) {
+        Ok(_) => println!("Wrote {} with {} files", path.display(), files.len()),
+        Err(e) => eprintln!("Failed to write {}: {}", path.display(), e),
+    }
+}
+
+/// Refresh the `files` of the manifest in `dir`: files no longer in the
+/// directory are removed and new ones added, the rest of the manifest
+/// is left as it is
+pub fn update(dir: PathBuf) {
+    let path = dir.join(MANIFEST);
+    let text = match read_to_string(&path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let package = match toml::from_str::<Package>(&text) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to parse {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let patterns = package.groups.values().flatten().cloned().collect::<Vec<_>>();
+    let grouped = groups::expand(&patterns, Some(&groups::local_files(&dir))).unwrap_or_default();
+    let available = package_files(&dir, &grouped);
+
+    // Files that moved to a group are removed too
+    let (kept, removed): (Vec<PackageFile>, Vec<PackageFile>) = package
+        .files
+        .into_iter()
+        .partition(|file| dir.join(file.src()).is_file() && !grouped.iter().any(|g| g == file.src()));
+    let added = available
+        .into_iter()
+        .filter(|file| !kept.iter().any(|f| f.src() == file))
+        .collect::<Vec<_>>();
+
+    if added.is_empty() && removed.is_empty() {
+        println!("{} is up to date", path.display());
+        return;
+    }
+
+    let mapped = kept.iter().any(|file| match file {
+        PackageFile::Mapped { .. } => true,
+        _ => false,
+    });
+    let mut files = kept;
+    files.extend(added.iter().map(|file| match mapped {
+        true => PackageFile::Mapped { src: file.clone(), dst: None },
+        false => PackageFile::Path(file.clone()),
+    }));
+
+    let updated = match FILES.find(&text) {
+        Some(m) => format!("{}{}{}", &text[..m.start()], files_array(&files), &text[m.end()..]),
+        None => {
+            eprintln!("Failed to find the files of {}", path.display());
+            return;
+        }
+    };
+    if !is_valid(&updated) {
+        return;
+    }
+
+    if let Err(e) = write_manifest(&path, &updated) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+        return;
+    }
+    for file in &added {
+        println!("+ {}", file);
+    }
+    for file in &removed {
+        println!("- {}", file.src());
+    }
+}
+
+/// The Godot files of the package in `dir`, skipping hidden files and the
+/// files of the optional groups
+fn package_files(dir: &Path, grouped: &[String]) -> Vec<String> {
+    groups::local_files(dir)
+        .into_iter()
+        .filter(|file| {
+            Path::new(file)
+                .extension()
+                .map(|ext| PACKAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .filter(|file| !grouped.contains(file))
+        .collect()
+}
+
+fn files_array(files: &[PackageFile]) -> String {
+    let mut text = String::from("files = [\n");
+    for file in files {
+        let entry = match file {
+            PackageFile::Path(path) => quote(path),
+            PackageFile::Mapped { src, dst: None } => format!("{{ src = {} }}", quote(src)),
+            PackageFile::Mapped { src, dst: Some(dst) } => format!("{{ src = {}, dst = {} }}", quote(src), quote(dst)),
+        };
+        text.push_str(&format!("    {},\n", entry));
+    }
+    text.push(']');
+    text
+}
+
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+/// The manifest passes `gdt verify`, problems are reported
+fn is_valid(text: &str) -> bool {
+    match toml::from_str::<Package>(text) {
+        Ok(package) => package.is_valid(),
+        Err(e) => {
+            eprintln!("Invalid manifest: {}", e);
+            false
+        }
+    }
+}
+
+fn write_manifest(path: &Path, text: &str) -> Result<()> {
+    File::create(path)?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// Ask for a value on stdin, an empty answer is the default
+fn ask(question: &str, default: &str) -> String {
+    match default.is_empty() {
+        true => print!("{}: ", question),
+        false => print!("{} [{}]: ", question, default),
+    }
+    let _ = stdout().flush();
+
+    let mut answer = String::new();
+    let _ = stdin().read_line(&mut answer);
+    match answer.trim() {
+        "" => default.to_string(),
+        answer => answer.to_string(),
+    }
+}
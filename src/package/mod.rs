@@ -1,20 +1,63 @@
-use std::fs::read_to_string;
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::env::current_dir;
+use std::fs::{create_dir_all, read, read_dir, read_to_string, remove_dir, remove_dir_all, remove_file, rename};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use crate::errors::Result;
+use crate::config;
+use crate::credentials;
+use crate::errors::{Error, Result};
+use crate::godot;
+use crate::project::{res_path, rewrite_references, DependencyGraph};
+use crate::remote::Fetcher;
 
+mod compare;
+mod extract;
+mod groups;
+mod inspect;
+mod manifest;
+mod ownership;
 mod package;
+mod project_settings;
+mod record;
+mod references;
+mod signing;
 
 use package::{Package, RootPath};
+use ownership::Ownership;
+use record::InstallRecord;
+
+pub use extract::extract;
+pub use inspect::Severity;
+pub use manifest::{init as init_manifest, update as update_manifest, ManifestFields};
+pub use signing::sign;
+
+const PACK_DIR: &'static str = "pack";
+/// Lines of the changelog shown for outdated packages
+const CHANGELOG_EXCERPT: usize = 5;
+
+pub struct InstallOptions {
+    pub allow_insecure: bool,
+    /// Overwrite locally modified files on update
+    pub force: bool,
+    /// Refuse packages with inspection findings at or above this level
+    pub deny: Option<Severity>,
+    /// Optional groups of files to install
+    pub with: Vec<String>,
+}
 
 
 pub fn verify(path: PathBuf) {
-    match read_to_string(path) {
+    match read_to_string(&path) {
         Ok(data) => match toml::from_str::<Package>(&data) {
-            Ok(package) => {
+            Ok(mut package) => {
                 if !package.is_valid() {
                     return;
                 }
+                let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+                if !has_only_package_references(&mut package, dir) {
+                    return;
+                }
                 eprintln!("Manifest: OK");
             }
             Err(e) => eprintln!("Error: {:#?}", e),
@@ -23,30 +66,617 @@ pub fn verify(path: PathBuf) {
     }
 }
 
-pub fn install_packages(requirements_file: Option<PathBuf>, path: Option<String>, update: bool) {
+/// Check the references in the package files, including the files of
+/// every group, report the ones to files outside the package
+fn has_only_package_references(package: &mut Package, dir: PathBuf) -> bool {
+    let groups = package.groups.keys().cloned().collect::<Vec<_>>();
+    if let Err(e) = package.select_groups(&groups, &RootPath::Local(dir.clone()), None) {
+        eprintln!("Failed to expand groups: {}", e);
+        return false;
+    }
+
+    match references::dangling(package, &dir) {
+        Some(dangling) => {
+            for reference in &dangling {
+                eprintln!("{}:{}: {} is not part of the package", reference.file, reference.line, reference.path);
+            }
+            dangling.is_empty()
+        }
+        None => false,
+    }
+}
+
+pub fn install_packages(requirements_file: Option<PathBuf>, path: Option<String>, update: bool, options: InstallOptions) {
+    let fetcher = match new_fetcher(options.allow_insecure) {
+        Some(f) => f,
+        None => return,
+    };
+
     let packages = match (requirements_file, path) {
         (Some(req_file), _) => {
             let package_list = read_requirements_file(req_file);
-            get_packages(package_list)
+            get_packages(&fetcher, package_list)
+        }
+        (_, Some(p)) => get_packages(&fetcher, vec![resolve_source(p)]),
+        // Update everything that was installed
+        (None, None) if update => {
+            let sources = installed_packages()
+                .iter()
+                .filter_map(|dst| InstallRecord::load(dst).ok().and_then(|r| r.source))
+                .collect();
+            get_packages(&fetcher, sources)
         }
-        (_, Some(p)) => get_packages(vec![p.into()]),
         _ => {
-            unreachable!("no requirements file or path to a package supplied");
+            eprintln!("No requirements file or path to a package supplied");
+            return;
         }
     };
 
-    for (root, package) in packages {
+    let project = config::load_project();
+    let mut ownership = Ownership::build();
+
+    for (source, root, mut package, manifest) in packages {
         if package.is_valid() {
+            let installed = installed_dir_of(&package);
+            let dst = match installed.clone().or_else(|| install_dir(&package, project.namespaced)) {
+                Some(d) => d,
+                None => {
+                    eprintln!("Refusing to install {}: the namespaced layout requires an author in the manifest", package.name());
+                    continue;
+                }
+            };
+
+            let signature = match signing::verify(&fetcher, &root, &package, &manifest, project.strict) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Refusing to install {}: {}", package.name(), e);
+                    continue;
+                }
+            };
+
+            // Updates keep the groups that were installed before
+            let mut selected = options.with.clone();
+            if let (true, Some(record)) = (update, installed.and_then(|d| InstallRecord::load(&d).ok())) {
+                selected.extend(record.groups.into_iter().filter(|g| !options.with.contains(g)));
+            }
+            let groups = match package.select_groups(&selected, &root, signature.as_ref()) {
+                Ok(g) => g,
+                Err(e) => {
+                    eprintln!("Refusing to install {}: {}", package.name(), e);
+                    continue;
+                }
+            };
+
+            let targets = package.targets(&dst);
+            let conflicts = ownership.overwritten(&package.qualified_name(), &targets);
+            if !conflicts.is_empty() {
+                eprintln!("Refusing to install {}, it would overwrite:", package.name());
+                for conflict in conflicts {
+                    eprintln!("    {}", conflict);
+                }
+                continue;
+            }
+
+            let ready = match update {
+                true => package.can_update(&dst),
+                false => package.can_install(&dst),
+            };
+            if !ready {
+                continue;
+            }
+
+            let staged = match package.stage(&fetcher, &root, signature.as_ref()) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Failed to download {}: {}", package.name(), e);
+                    continue;
+                }
+            };
+
+            // Inspect the exact files that are about to be installed
+            if !inspect_package(staged.path(), &package, options.deny) {
+                eprintln!("Refusing to install {}: inspection found issues at or above {}", package.name(), options.deny.unwrap());
+                continue;
+            }
+
+            let mut record = InstallRecord::new(&fetcher, &source, &root);
+            record.groups = groups;
             match update {
-                true => package.update(root, format!("./pack/{}", package.safe_name()).into()),
-                false => package.install(root, format!("./pack/{}", package.safe_name()).into()),
+                true => package.update(&manifest, staged.path(), dst, record, options.force),
+                false => package.install(&manifest, staged.path(), dst, record),
             }
+
+            // Later packages in the same run must not overwrite this one
+            ownership = Ownership::build();
         } else {
             eprintln!("Invalid manifest: {:?}", package.name());
         }
     }
 }
 
+/// Remove an installed package and every file it installed
+pub fn uninstall(name: String, force: bool) {
+    let dst = match find_installed(&name) {
+        Some(d) => d,
+        None => return,
+    };
+
+    let record = InstallRecord::load(&dst);
+    let users = project_references(&dst, record.as_ref().ok());
+    if !users.is_empty() {
+        eprintln!("{} is still used by the project:", name);
+        for (user, line, file) in &users {
+            eprintln!("  {}:{} refers to {}", user, line, file);
+        }
+        if !force {
+            eprintln!("Not uninstalling {}, use --force to uninstall it anyway", name);
+            return;
+        }
+    }
+
+    match record {
+        Ok(record) => {
+            record.remove_files();
+            project_settings::unregister(&record);
+        }
+        Err(e) => {
+            eprintln!("No install record for {}, removing {:?}: {}", name, dst, e);
+            // Files mapped outside the package directory are only known from the manifest
+            if let Ok(package) = local_package(dst.join(package::MANIFEST)) {
+                for target in package.targets(&dst).iter().filter(|t| !t.starts_with(&dst)) {
+                    let _ = remove_file(target);
+                }
+            }
+        }
+    }
+
+    match remove_dir_all(&dst) {
+        Ok(_) => println!("Uninstalled: {}", name),
+        Err(e) => eprintln!("Failed to remove {:?}: {}", dst, e),
+    }
+
+    // Remove the namespace directory once its last package is gone
+    if let Some(parent) = dst.parent().filter(|p| *p != Path::new(PACK_DIR)) {
+        let _ = remove_dir(parent);
+    }
+}
+
+/// The references of the project's scenes, resources and scripts to the files
+/// of the package installed in `dst`, as (referencing file, line, package file)
+fn project_references(dst: &Path, record: Option<&InstallRecord>) -> Vec<(String, usize, String)> {
+    let mut files = groups::local_files(dst)
+        .iter()
+        .map(|file| res_path(&dst.join(file)))
+        .collect::<BTreeSet<_>>();
+    if let Some(record) = record {
+        files.extend(record.files.keys().map(|file| res_path(Path::new(file))));
+    }
+
+    // The settings the package registered are removed with it
+    let project_file = res_path(Path::new(godot::PROJECT_FILE));
+    let project = godot::ConfigFile::load(Path::new(godot::PROJECT_FILE)).ok();
+    let registered = |user: &str, line: usize, file: &str| match (record, &project) {
+        (Some(record), Some(project)) if user == project_file => project_settings::is_registered(project, record, line, file),
+        _ => false,
+    };
+
+    let graph = DependencyGraph::build(Path::new("."));
+    let mut users = Vec::new();
+    for file in &files {
+        for (user, line) in graph.references_to(file) {
+            if !files.contains(user) && !registered(user, line, file) {
+                users.push((user.to_string(), line, file.clone()));
+            }
+        }
+    }
+    users.sort();
+    users
+}
+
+/// Show which package owns a file, or without a path, list the files in
+/// the install directories no package owns and files claimed by several
+pub fn owns(path: Option<String>) {
+    let ownership = Ownership::build();
+
+    let path = match path {
+        Some(p) => p,
+        None => {
+            for file in ownership.unowned() {
+                println!("unowned: {}", file.display());
+            }
+            for (file, owners) in ownership.conflicts() {
+                println!("conflict: {} is claimed by {}", file, owners.join(", "));
+            }
+            return;
+        }
+    };
+
+    // Accept `res://` paths and absolute paths inside the project
+    let mut file = PathBuf::from(path.trim_start_matches("res://"));
+    if let Ok(cwd) = current_dir() {
+        if let Ok(relative) = file.strip_prefix(&cwd) {
+            file = relative.to_path_buf();
+        }
+    }
+
+    match ownership.owners(&file) {
+        [] => println!("{} is not owned by any package", file.display()),
+        [owner] => println!("{} is owned by {}", file.display(), owner),
+        owners => println!("{} is claimed by {}", file.display(), owners.join(", ")),
+    }
+}
+
+/// The source of an installed package, if `source` is the name of one
+fn resolve_source(source: String) -> String {
+    let recorded = match installed_matching(&source).as_slice() {
+        [dst] => InstallRecord::load(dst).ok().and_then(|r| r.source),
+        _ => None,
+    };
+
+    match recorded {
+        Some(recorded) => recorded,
+        None => source,
+    }
+}
+
+/// List the files of each installed package that were
+/// modified, deleted or added since it was installed
+pub fn status() {
+    for dst in installed_packages() {
+        let name = dst.strip_prefix(PACK_DIR).map(|p| record::project_path(p)).unwrap_or_default();
+        let record = match InstallRecord::load(&dst) {
+            Ok(r) => r,
+            Err(_) => {
+                println!("{}: no install record", name);
+                continue;
+            }
+        };
+
+        let changes = record.changes(&dst);
+        if changes.is_empty() {
+            println!("{}: unmodified", name);
+            continue;
+        }
+
+        println!("{}:", name);
+        for file in &changes.modified {
+            println!("    modified: {}", file);
+        }
+        for file in &changes.deleted {
+            println!("    deleted:  {}", file);
+        }
+        for file in &changes.added {
+            println!("    added:    {}", file);
+        }
+    }
+}
+
+/// Show what changes between an installed package and the version
+/// available from where it was installed, or from `to`
+pub fn diff(name: String, to: Option<String>) {
+    let dst = match find_installed(&name) {
+        Some(d) => d,
+        None => return,
+    };
+    let installed = match local_package(dst.join(package::MANIFEST)) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{} is not installed: {}", name, e);
+            return;
+        }
+    };
+
+    let source = match to.or_else(|| InstallRecord::load(&dst).ok().and_then(|r| r.source)) {
+        Some(s) => s,
+        None => {
+            eprintln!("No source recorded for {}, use --to <source>", name);
+            return;
+        }
+    };
+
+    let fetcher = match new_fetcher(false) {
+        Some(f) => f,
+        None => return,
+    };
+
+    let (_, root, candidate, _) = match get_packages(&fetcher, vec![source]).pop() {
+        Some(p) => p,
+        None => return,
+    };
+
+    // Removed when dropped
+    let tmp = match tempfile::Builder::new().prefix("gdt-diff-").tempdir() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Failed to create a temporary directory: {}", e);
+            return;
+        }
+    };
+    match candidate.fetch(&fetcher, &root, tmp.path()) {
+        Ok(_) => {
+            if !compare::compare(&dst, &installed, tmp.path(), &candidate) {
+                println!("No changes");
+            }
+        }
+        Err(e) => eprintln!("Failed to fetch {}: {}", candidate.name(), e),
+    }
+}
+
+/// Check every installed package for a newer version at the source it was
+/// installed from, exits with an error if any of them are outdated or
+/// can't be checked
+pub fn outdated() {
+    let fetcher = match new_fetcher(false) {
+        Some(f) => f,
+        None => return,
+    };
+
+    let mut rows = vec![("Package".to_string(), "Installed".to_string(), "Latest".to_string())];
+    let mut changelogs = Vec::new();
+    let mut outdated = 0;
+    let mut unchecked = 0;
+
+    for dst in installed_packages() {
+        let installed = match local_package(dst.join(package::MANIFEST)) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("Failed to read manifest in {:?}: {}", dst, e);
+                continue;
+            }
+        };
+
+        let source = match InstallRecord::load(&dst).ok().and_then(|r| r.source) {
+            Some(s) => s,
+            None => {
+                unchecked += 1;
+                rows.push((installed.name().to_string(), installed.version().to_string(), "unknown source".to_string()));
+                continue;
+            }
+        };
+
+        let latest = match get_packages(&fetcher, vec![source]).pop() {
+            Some((_, _, latest, _)) => latest,
+            None => {
+                unchecked += 1;
+                rows.push((installed.name().to_string(), installed.version().to_string(), "unavailable".to_string()));
+                continue;
+            }
+        };
+
+        let known = installed.version().parse::<f32>().is_ok() && latest.version().parse::<f32>().is_ok();
+        if !known {
+            unchecked += 1;
+            rows.push((installed.name().to_string(), installed.version().to_string(), format!("{} (unknown version)", latest.version())));
+        } else if latest.is_newer_than(&installed) {
+            outdated += 1;
+            if let Some(ref changelog) = latest.changelog {
+                changelogs.push((latest.name().to_string(), changelog.clone()));
+            }
+            rows.push((installed.name().to_string(), installed.version().to_string(), latest.version().to_string()));
+        } else {
+            rows.push((installed.name().to_string(), installed.version().to_string(), "up to date".to_string()));
+        }
+    }
+
+    let width = |col: fn(&(String, String, String)) -> &String| rows.iter().map(|r| col(r).len()).max().unwrap_or(0);
+    let (name_width, version_width) = (width(|r| &r.0), width(|r| &r.1));
+    for (name, installed, latest) in &rows {
+        println!("{:name_width$}  {:version_width$}  {}", name, installed, latest, name_width = name_width, version_width = version_width);
+    }
+
+    for (name, changelog) in &changelogs {
+        println!("\n{}:", name);
+        for line in changelog.trim().lines().take(CHANGELOG_EXCERPT) {
+            println!("    {}", line);
+        }
+    }
+
+    if unchecked > 0 {
+        eprintln!("\n{} packages could not be checked", unchecked);
+    }
+    if outdated > 0 || unchecked > 0 {
+        process::exit(1);
+    }
+}
+
+/// Move packages installed to `pack/<name>` to `pack/<author>/<name>`,
+/// rewriting the `res://` references to them, and switch the project
+/// to the namespaced layout
+pub fn migrate_layout() {
+    let mut replacements = Vec::new();
+
+    for dst in installed_packages() {
+        // Already namespaced
+        if dst.parent() != Some(Path::new(PACK_DIR)) {
+            continue;
+        }
+
+        let package = match local_package(dst.join(package::MANIFEST)) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("Skipping {:?}, failed to read manifest: {}", dst, e);
+                continue;
+            }
+        };
+
+        let new_dst = match install_dir(&package, true) {
+            Some(d) => d,
+            None => {
+                eprintln!("Skipping {}, no author in the manifest", package.name());
+                continue;
+            }
+        };
+
+        let namespace_dir = new_dst.parent().unwrap();
+        if new_dst.exists() || is_package_dir(namespace_dir) {
+            eprintln!("Skipping {}, {:?} is already in use", package.name(), new_dst);
+            continue;
+        }
+
+        if let Err(e) = create_dir_all(namespace_dir).and_then(|_| rename(&dst, &new_dst)) {
+            eprintln!("Failed to move {:?} to {:?}: {}", dst, new_dst, e);
+            continue;
+        }
+
+        if let Ok(mut record) = InstallRecord::load(&new_dst) {
+            if let Err(e) = record.move_files(&new_dst, &dst, &new_dst) {
+                eprintln!("Failed to move the merge base of {}: {}", package.name(), e);
+            }
+            if let Err(e) = record.save(&new_dst) {
+                eprintln!("Failed to update install record of {}: {}", package.name(), e);
+            }
+        }
+
+        println!("Moved {} to {}", dst.display(), new_dst.display());
+        replacements.push((format!("{}/", res_path(&dst)), format!("{}/", res_path(&new_dst))));
+    }
+
+    match rewrite_references(Path::new("."), &replacements, false) {
+        Ok(edits) => {
+            for edit in edits {
+                println!("Updated {}:{}: {} -> {}", edit.file.display(), edit.line, edit.from, edit.to);
+            }
+        }
+        Err(e) => eprintln!("Failed to update references: {}", e),
+    }
+
+    if let Err(e) = config::set_project_flag("namespaced", true) {
+        eprintln!("Failed to enable the namespaced layout in gdt.toml: {}", e);
+    }
+}
+
+/// Where a package is installed, `pack/<name>`, or `pack/<author>/<name>`
+/// in projects using the namespaced layout
+fn install_dir(package: &Package, namespaced: bool) -> Option<PathBuf> {
+    install_path(package.name(), package.namespace().as_ref().map(|n| n.as_str()), namespaced)
+}
+
+/// `install_dir` of a package by its name and author
+fn install_path(name: &str, author: Option<&str>, namespaced: bool) -> Option<PathBuf> {
+    match (namespaced, author) {
+        (false, _) => Some(PathBuf::from(PACK_DIR).join(name.to_lowercase())),
+        (true, Some(author)) => Some(PathBuf::from(PACK_DIR).join(author.to_lowercase()).join(name.to_lowercase())),
+        (true, None) => None,
+    }
+}
+
+/// Where an already installed version of `package` is, in either layout
+fn installed_dir_of(package: &Package) -> Option<PathBuf> {
+    installed_packages().into_iter().find(|dst| match local_package(dst.join(package::MANIFEST)) {
+        Ok(installed) => installed.safe_name() == package.safe_name() && installed.namespace() == package.namespace(),
+        Err(_) => false,
+    })
+}
+
+/// Find an installed package by `name` or `author/name`
+fn find_installed(name: &str) -> Option<PathBuf> {
+    let matches = installed_matching(name);
+    match matches.len() {
+        0 => {
+            eprintln!("{} is not installed", name);
+            None
+        }
+        1 => matches.into_iter().next(),
+        _ => {
+            eprintln!("{} is ambiguous, use one of:", name);
+            for dst in matches {
+                eprintln!("    {}", dst.strip_prefix(PACK_DIR).map(|p| record::project_path(p)).unwrap_or_default());
+            }
+            None
+        }
+    }
+}
+
+/// The install directories of the packages called `name` or `author/name`
+fn installed_matching(name: &str) -> Vec<PathBuf> {
+    let name = name.to_lowercase();
+    installed_packages()
+        .into_iter()
+        .filter(|dst| {
+            let relative = dst.strip_prefix(PACK_DIR).map(|p| record::project_path(p)).unwrap_or_default();
+            relative == name || dst.file_name().map(|n| n.to_string_lossy() == name.as_str()).unwrap_or(false)
+        })
+        .collect()
+}
+
+fn is_package_dir(dir: &Path) -> bool {
+    dir.join(package::MANIFEST).exists() || InstallRecord::exists(dir)
+}
+
+/// The install directory of every package in `./pack`, in either layout
+fn installed_packages() -> Vec<PathBuf> {
+    let mut packages = Vec::new();
+    for dir in sub_dirs(Path::new(PACK_DIR)) {
+        match is_package_dir(&dir) {
+            true => packages.push(dir),
+            false => packages.extend(sub_dirs(&dir).into_iter().filter(|d| is_package_dir(d))),
+        }
+    }
+    packages.sort();
+    packages
+}
+
+fn sub_dirs(dir: &Path) -> Vec<PathBuf> {
+    match read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Scan the scripts of a package for risky API usage
+pub fn inspect(source: String, deny: Option<Severity>) {
+    let fetcher = match new_fetcher(false) {
+        Some(f) => f,
+        None => return,
+    };
+
+    let mut passed = true;
+    for (_, root, package, _) in get_packages(&fetcher, vec![source]) {
+        match package.stage(&fetcher, &root, None) {
+            Ok(staged) => passed &= inspect_package(staged.path(), &package, deny),
+            Err(e) => {
+                eprintln!("Failed to download {}: {}", package.name(), e);
+                passed = false;
+            }
+        }
+    }
+
+    if !passed {
+        process::exit(1);
+    }
+}
+
+/// Print the findings for a package, returns false if
+/// any of them are at or above the `deny` level. `dir` holds the package files
+fn inspect_package(dir: &Path, package: &Package, deny: Option<Severity>) -> bool {
+    let findings = match inspect::inspect(dir, package) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Failed to inspect {}: {}", package.name(), e);
+            return deny.is_none();
+        }
+    };
+
+    for finding in &findings {
+        println!("{}: {}", package.name(), finding);
+    }
+
+    match deny {
+        Some(level) => findings.iter().all(|f| f.severity < level),
+        None => true,
+    }
+}
+
+fn new_fetcher(allow_insecure: bool) -> Option<Fetcher> {
+    match Fetcher::new(&config::load().remote, credentials::load(), allow_insecure) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            eprintln!("Failed to create http client: {}", e);
+            None
+        }
+    }
+}
+
 fn read_requirements_file(path: PathBuf) -> Vec<String> {
     let data = match read_to_string(path) {
         Ok(d) => d,
@@ -58,35 +688,46 @@ fn read_requirements_file(path: PathBuf) -> Vec<String> {
     data.trim().split('\n').map(|l| l.trim().to_string()).collect()
 }
 
-fn get_packages(package_paths: Vec<String>) -> Vec<(RootPath, Package)> {
+/// Each line is a package path or url, optionally followed by
+/// whitespace separated mirror urls for remote packages. Each package comes
+/// with its manifest as it was read, to verify and install the same bytes
+fn get_packages(fetcher: &Fetcher, package_paths: Vec<String>) -> Vec<(String, RootPath, Package, Vec<u8>)> {
     let mut packages = Vec::new();
 
     for line in package_paths {
         let line = line.trim();
-        if line.starts_with("#") {
+        if line.is_empty() || line.starts_with("#") {
             continue
         }
         if line.starts_with("/") {
             // local file
             let path: PathBuf = line.into();
-            match local_package(path.join("manifest.toml")) {
-                Ok(package) => packages.push((RootPath::Local(path), package)),
+            match read_package(&path) {
+                Ok((package, data)) => packages.push((line.to_string(), RootPath::Local(path), package, data)),
                 Err(e) => eprintln!("failed to load (local) package: {:?}", e),
             }
         } else {
-            // remote file full url
-            let mut url = match line.starts_with("https://") {
-                true => line.to_string(),
-                false => to_github_raw(line),
+            // remote file full url, followed by any mirrors
+            let urls = match line.split_whitespace().map(to_base_url).collect::<Result<Vec<_>>>() {
+                Ok(u) => u,
+                Err(e) => {
+                    eprintln!("failed to load (remote) package: {}", e);
+                    continue;
+                }
             };
 
-            if !url.ends_with("/") {
-                url.push('/');
-            }
-
-            match remote_package(&format!("{}manifest.toml", url)) {
-                Ok(package) => packages.push((RootPath::Remote(url), package)),
-                Err(_e) => eprintln!("failed to load (remote) package. Invalid or missing manifest file"),
+            match remote_package(fetcher, &urls) {
+                Ok((mut package, data)) => {
+                    let mut urls = urls;
+                    for mirror in package.mirrors.drain(..) {
+                        match to_base_url(&mirror) {
+                            Ok(url) => urls.push(url),
+                            Err(e) => eprintln!("Ignoring mirror: {}", e),
+                        }
+                    }
+                    packages.push((line.to_string(), RootPath::Remote(urls), package, data))
+                }
+                Err(e) => eprintln!("failed to load (remote) package. Invalid or missing manifest file: {}", e),
             }
         }
     }
@@ -99,15 +740,40 @@ fn local_package(path: PathBuf) -> Result<Package> {
     Ok(toml::from_str::<Package>(&data)?)
 }
 
-fn remote_package(url: &str) -> Result<Package> {
-    let data = reqwest::blocking::get(url)?.text()?;
-    Ok(toml::from_str::<Package>(&data)?)
+fn read_package(dir: &Path) -> Result<(Package, Vec<u8>)> {
+    let data = read(dir.join(package::MANIFEST))?;
+    Ok((toml::from_slice::<Package>(&data)?, data))
+}
+
+fn remote_package(fetcher: &Fetcher, urls: &[String]) -> Result<(Package, Vec<u8>)> {
+    let data = fetcher.text(urls, package::MANIFEST)?;
+    Ok((toml::from_str::<Package>(&data)?, data.into_bytes()))
 }
 
-fn to_github_raw(path: &str) -> String {
-    let parts = path.split("/").collect::<Vec<_>>();
-    if parts.len() != 2 {
-        eprintln!("{:?}", "invalid path");
+fn to_base_url(path: &str) -> Result<String> {
+    let mut url = match path.starts_with("https://") || path.starts_with("http://") {
+        true => path.to_string(),
+        false => to_github_raw(path)?,
+    };
+
+    if !url.ends_with("/") {
+        url.push('/');
+    }
+    Ok(url)
+}
+
+/// `user/package`, optionally followed by `@<branch or tag>`
+fn to_github_raw(path: &str) -> Result<String> {
+    let (repo_path, git_ref) = match path.find('@') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => (path, "master"),
+    };
+
+    match repo_path.split('/').collect::<Vec<_>>().as_slice() {
+        [user, package] if !user.is_empty() && !package.is_empty() && !git_ref.is_empty() => Ok(format!(
+            "https://raw.githubusercontent.com/{}/godot-packages/{}/{}/",
+            user, git_ref, package
+        )),
+        _ => Err(Error::InvalidSource(path.to_string())),
     }
-    format!("https://raw.githubusercontent.com/{}/godot-packages/master/{}/", parts[0], parts[1])
 }
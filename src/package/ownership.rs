@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+use super::package::MANIFEST;
+use super::record::{project_path, InstallRecord, META_DIR};
+use super::signing::SIGNATURE;
+use super::{installed_packages, local_package};
+
+/// Directories packages install into
+pub const INSTALL_ROOTS: [&'static str; 2] = ["pack", "addons"];
+
+/// Which installed package owns each file, built from the install
+/// records and manifests of every installed package. Packages are
+/// identified by `author/name`
+pub struct Ownership {
+    owners: BTreeMap<String, Vec<String>>,
+}
+
+impl Ownership {
+    pub fn build() -> Self {
+        let mut owners: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for dst in installed_packages() {
+            let name = match local_package(dst.join(MANIFEST)) {
+                Ok(package) => package.qualified_name(),
+                Err(_) => dst.file_name().unwrap().to_string_lossy().to_string(),
+            };
+
+            let mut files = match InstallRecord::load(&dst) {
+                Ok(record) => record.files.keys().cloned().collect::<Vec<_>>(),
+                Err(_) => match local_package(dst.join(MANIFEST)) {
+                    Ok(package) => package.targets(&dst).iter().map(|f| project_path(f)).collect(),
+                    Err(_) => Vec::new(),
+                },
+            };
+
+            // The package's own metadata
+            files.push(project_path(&dst.join(MANIFEST)));
+            files.push(project_path(&dst.join(SIGNATURE)));
+            let mut meta = Vec::new();
+            walk(&dst.join(META_DIR), &mut meta);
+            files.extend(meta.iter().map(|f| project_path(f)));
+
+            for file in files {
+                let file_owners = owners.entry(file).or_default();
+                if !file_owners.contains(&name) {
+                    file_owners.push(name.clone());
+                }
+            }
+        }
+
+        Self { owners }
+    }
+
+    /// The packages owning `path`, import files generated by Godot
+    /// belong to the package owning the imported file
+    pub fn owners(&self, path: &Path) -> &[String] {
+        let file = project_path(path);
+        let owners = match self.owners.get(&file) {
+            Some(owners) => Some(owners),
+            None if file.ends_with(".import") => self.owners.get(&file[..file.len() - ".import".len()]),
+            None => None,
+        };
+        owners.map(|o| o.as_slice()).unwrap_or(&[])
+    }
+
+    /// The files in `paths` that `package`, by its qualified name, would
+    /// overwrite although they belong to another package, or to the user
+    pub fn overwritten(&self, package: &str, paths: &[PathBuf]) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        for path in paths {
+            let owners = self.owners(path);
+            if owners.iter().any(|owner| owner == package) {
+                continue;
+            }
+
+            if !owners.is_empty() {
+                conflicts.push(format!("{} (owned by {})", path.display(), owners.join(", ")));
+            } else if path.exists() {
+                conflicts.push(format!("{} (not owned by any package)", path.display()));
+            }
+        }
+
+        conflicts
+    }
+
+    /// Paths claimed by more than one package
+    pub fn conflicts(&self) -> Vec<(&String, &Vec<String>)> {
+        self.owners.iter().filter(|(_, owners)| owners.len() > 1).collect()
+    }
+
+    /// Files in the install directories that no package owns
+    pub fn unowned(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for root in INSTALL_ROOTS.iter() {
+            walk(Path::new(root), &mut files);
+        }
+        files.sort();
+        files.into_iter().filter(|f| self.owners(f).is_empty()).collect()
+    }
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        match path.is_dir() {
+            true => walk(&path, files),
+            false => files.push(path),
+        }
+    }
+}
@@ -1,33 +1,124 @@
-use std::fs::{copy, create_dir_all, remove_file, remove_dir, File};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fs::{copy, create_dir_all, read_to_string, remove_file, remove_dir, File};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
 
 use regex::Regex;
 use log::{error, info};
 use serde_derive::Deserialize;
 use lazy_static::lazy_static;
+use tempfile::TempDir;
 
-const MANIFEST: &'static str = "manifest.toml";
+pub const MANIFEST: &'static str = "manifest.toml";
+/// Settings files of the project, a package never installs these
+const PROTECTED_FILES: [&'static str; 4] = [godot::PROJECT_FILE, "override.cfg", "export_presets.cfg", config::PROJECT_CONFIG_FILE];
 
 
 lazy_static! {
     static ref MANIFEST_NAME: Regex = Regex::new(r"^[a-zA-Z0-9]+[a-zA-Z0-9\-_]*$").unwrap();
 }
 
+use crate::config;
+use crate::diff;
 use crate::errors::Result;
+use crate::godot;
+use crate::remote::Fetcher;
+
+use super::groups;
+use super::project_settings;
+use super::record::InstallRecord;
+use super::signing::Signature;
 
 pub enum RootPath {
     Local(PathBuf),
-    Remote(String),
+    /// Base url followed by any mirrors
+    Remote(Vec<String>),
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Package {
     name: String,
+    author: Option<String>,
     description: String,
     usage: String,
     version: String,
 
-    pub files: Vec<String>,
+    pub files: Vec<PackageFile>,
+
+    /// Optional files, such as examples, installed with `--with <group>`.
+    /// Paths or patterns where `*` matches within a directory and `**`
+    /// matches across directories
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+
+    /// Autoload singletons to add to the project, name to package file
+    #[serde(default)]
+    pub autoload: BTreeMap<String, String>,
+
+    /// The `plugin.cfg` of each editor plugin to enable
+    #[serde(default)]
+    pub editor_plugins: Vec<String>,
+
+    /// Input map actions the package uses, added to the project unless
+    /// it already has an action with the same name
+    #[serde(default)]
+    pub input: BTreeMap<String, InputAction>,
+
+    /// `res://` paths outside the package its files may refer to, such as
+    /// the files of a package it depends on. `*` matches within a directory
+    /// and `**` across directories
+    #[serde(default)]
+    pub external: Vec<String>,
+
+    /// Base urls to fall back on if the package source is unavailable
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+
+    /// Notes on what changed, most recent version first
+    pub changelog: Option<String>,
+}
+
+/// A file of the package, either a path that is installed at the same path
+/// inside the package directory, or a `{ src = "...", dst = "..." }` table
+/// mapping it to a path relative to the project root. TOML arrays can't mix
+/// strings and tables, so `dst` is optional for the unmapped files of a
+/// manifest that maps some of them
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum PackageFile {
+    Path(String),
+    Mapped { src: String, dst: Option<String> },
+}
+
+/// Default events of an input map action
+#[derive(Deserialize, Debug)]
+pub struct InputAction {
+    /// Key names as the editor shows them, with optional modifiers, e.g. `Space` or `Ctrl+S`
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// Joypad button indices
+    #[serde(default)]
+    pub buttons: Vec<i64>,
+    pub deadzone: Option<f64>,
+}
+
+impl PackageFile {
+    /// The path of the file in the package source
+    pub fn src(&self) -> &str {
+        match self {
+            PackageFile::Path(path) => path,
+            PackageFile::Mapped { src, .. } => src,
+        }
+    }
+
+    /// Where the file is installed for a package installed to `dst`
+    pub fn target(&self, dst: &Path) -> PathBuf {
+        match self {
+            PackageFile::Path(path) => dst.join(path),
+            PackageFile::Mapped { src, dst: None } => dst.join(src),
+            PackageFile::Mapped { dst: Some(mapped), .. } => PathBuf::from(mapped),
+        }
+    }
 }
 
 impl Package {
@@ -39,81 +130,252 @@ impl Package {
         &self.name
     }
 
-    fn load(&self, src: RootPath, dst: PathBuf) {
-        let mut copied_files = Vec::new();
+    /// `author/name`, or the name alone for packages without an author,
+    /// packages by different authors can share a name
+    pub fn qualified_name(&self) -> String {
+        match self.author {
+            Some(ref author) => format!("{}/{}", author, self.name),
+            None => self.name.clone(),
+        }
+    }
 
-        for file in &self.files {
-            let dst_full = dst.join(&file);
-            if let Some(path) = create_dir(&dst_full) {
-                copied_files.push(path);
-            }
+    /// The namespace of the package in the `pack/<author>/<name>` layout
+    pub fn namespace(&self) -> Option<String> {
+        self.author.as_ref().map(|author| author.to_lowercase())
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Where every file of the package is installed for a package installed to `dst`
+    pub fn targets(&self, dst: &Path) -> Vec<PathBuf> {
+        self.files.iter().map(|file| file.target(dst)).collect()
+    }
+
+    /// Where the package file `src` is installed
+    pub fn target_of(&self, src: &str, dst: &Path) -> Option<PathBuf> {
+        self.files.iter().find(|file| file.src() == src).map(|file| file.target(dst))
+    }
 
-            let res = match src {
-                RootPath::Local(ref path) => copy_local(&path.join(&file), &dst_full),
-                RootPath::Remote(ref url) => copy_remote(url, &file, &dst_full),
+    /// Add the files of the selected optional groups to the files of the
+    /// package, returns the groups the package has
+    pub fn select_groups(&mut self, selected: &[String], src: &RootPath, signature: Option<&Signature>) -> Result<Vec<String>> {
+        let available = match src {
+            RootPath::Local(path) => Some(groups::local_files(path)),
+            RootPath::Remote(_) => signature.map(groups::signed_files),
+        };
+
+        let mut added = Vec::new();
+        for name in selected {
+            let patterns = match self.groups.get(name) {
+                Some(p) => p,
+                None => {
+                    eprintln!("{} has no group {}", self.name, name);
+                    continue;
+                }
             };
 
-            match res {
-                Ok(_) => copied_files.push(dst_full),
-                Err(e) => {
-                    error!("Failed to copy file: {:?}, Rolling back", e);
-                    rollback(copied_files);
-                    return;
+            for file in groups::expand(patterns, available.as_ref().map(|a| a.as_slice()))? {
+                if !is_installable(&file) {
+                    eprintln!("Skipping \"{}\" in group {}, hidden files and project settings aren't installed", file, name);
+                    continue;
+                }
+                if !self.files.iter().any(|f| f.src() == file) {
+                    self.files.push(PackageFile::Path(file));
                 }
             }
+            added.push(name.clone());
         }
-    }
 
-    pub fn update(&self, src: RootPath, dst: PathBuf) {
-        // Only update existing packages
-        if !dst.exists() {
-            return
-        }
-
-        // Check the local manifest for a lower version
-        if !self.version_is_increased(&dst) {
-            return
-        }
+        Ok(added)
+    }
 
-        let dst_full = dst.join(MANIFEST);
-        let _res = match src {
-            RootPath::Local(ref path) => copy_local(&path.join(MANIFEST), &dst_full),
-            RootPath::Remote(ref url) => copy_remote(url, MANIFEST, &dst_full),
-        };
+    /// Download every file of the package to a temporary directory, checking
+    /// them against the signature. Nothing in the project is touched until
+    /// every file has arrived
+    pub fn stage(&self, fetcher: &Fetcher, src: &RootPath, signature: Option<&Signature>) -> Result<TempDir> {
+        let staged = tempfile::Builder::new().prefix("gdt-").tempdir()?;
+        let files = self.files.iter().map(|file| (file.src(), staged.path().join(file.src()))).collect();
+        fetch_files(fetcher, src, files, signature)?;
+        Ok(staged)
+    }
 
-        self.load(src, dst);
-        println!("Updated: {}", self.name);
+    /// Copy every file of the package to its source path inside `dir`,
+    /// ignoring the destination mapping, to look at it without installing
+    pub fn fetch(&self, fetcher: &Fetcher, src: &RootPath, dir: &Path) -> Result<Vec<PathBuf>> {
+        let files = self.files.iter().map(|file| (file.src(), dir.join(file.src()))).collect();
+        fetch_files(fetcher, src, files, None)
     }
 
-    pub fn install(&self, src: RootPath, dst: PathBuf) {
-        // Copy the manifest
-        let dst_full = dst.join(MANIFEST);
-        let _res = match src {
-            RootPath::Local(ref path) => copy_local(&path.join(MANIFEST), &dst_full),
-            RootPath::Remote(ref url) => copy_remote(url, MANIFEST, &dst_full),
-        };
+    /// Copy the staged files of the package to where they're installed,
+    /// restoring what was there if any of them fail. Returns the installed files
+    fn load(&self, staged: &Path, dst: &Path) -> Result<Vec<PathBuf>> {
+        let files = self.files.iter().map(|file| (staged.join(file.src()), file.target(dst))).collect();
+        install_files(files)
+    }
 
-        // Only install new packages
-        let existing = self.existing_files(&dst);
+    /// An update is only installed over an older version
+    pub fn can_update(&self, dst: &PathBuf) -> bool {
+        dst.exists() && self.version_is_increased(dst)
+    }
 
+    /// A new install never overwrites existing files
+    pub fn can_install(&self, dst: &PathBuf) -> bool {
+        let existing = self.existing_files(dst);
         if existing.len() > 0 {
             for file in existing {
                 info!("file exists: {}", file);
             }
             println!("already installed {}", self.name);
-            return;
+            return false;
+        }
+        true
+    }
+
+    /// Install the files staged with `stage` over an older version
+    pub fn update(&self, manifest: &[u8], staged: &Path, dst: PathBuf, mut record: InstallRecord, force: bool) {
+        // Local changes are merged with the new version, unless forced
+        let previous = InstallRecord::load(&dst).ok();
+        let mut local_changes = Vec::new();
+        if let (Some(previous), false) = (&previous, force) {
+            let mut unmergeable = Vec::new();
+            let modified = self.targets(&dst).into_iter().filter(|path| previous.is_modified(path));
+
+            for path in modified {
+                match (InstallRecord::base_text(&dst, &path), read_to_string(&path)) {
+                    (Some(base), Ok(local)) => local_changes.push((path, base, local)),
+                    _ => unmergeable.push(path),
+                }
+            }
+
+            if !unmergeable.is_empty() {
+                eprintln!("Not updating {}, locally modified files can't be merged (use --force to overwrite):", self.name);
+                for path in unmergeable {
+                    eprintln!("    {}", path.display());
+                }
+                return;
+            }
+        }
+
+        let installed = match self.load(staged, &dst) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Failed to update {}: {}", self.name, e);
+                return;
+            }
+        };
+
+        // Files the new version no longer has are removed, unless they were changed
+        if let Some(previous) = &previous {
+            let kept = previous.remove_stale(&installed);
+            if !kept.is_empty() {
+                eprintln!("Locally modified files no longer in {}, kept:", self.name);
+                for file in kept.keys() {
+                    eprintln!("    {}", file);
+                }
+            }
+            record.files.extend(kept);
+        }
+
+        let _ = write_manifest(manifest, &dst);
+        project_settings::register(self, &dst, previous.as_ref(), &mut record);
+        write_record(&dst, &installed, record);
+
+        let mut conflicts = Vec::new();
+        for (path, base, local) in local_changes {
+            match merge_local_changes(&path, &base, &local) {
+                Ok(0) => println!("Merged local changes: {}", path.display()),
+                Ok(_) => conflicts.push(path),
+                Err(e) => {
+                    eprintln!("Failed to merge {}: {}", path.display(), e);
+                    conflicts.push(path);
+                }
+            }
         }
 
-        self.load(src, dst);
+        println!("Updated: {}", self.name);
+        if !conflicts.is_empty() {
+            println!("Merge conflicts, local versions kept as .orig:");
+            for path in conflicts {
+                println!("    {}", path.display());
+            }
+        }
+    }
+
+    /// Install the files staged with `stage`
+    pub fn install(&self, manifest: &[u8], staged: &Path, dst: PathBuf, mut record: InstallRecord) {
+        let installed = match self.load(staged, &dst) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Failed to install {}: {}", self.name, e);
+                return;
+            }
+        };
+
+        let _ = write_manifest(manifest, &dst);
+        project_settings::register(self, &dst, None, &mut record);
+        write_record(&dst, &installed, record);
         println!("Installed: {}", self.name);
     }
 
     pub fn is_valid(&self) -> bool {
-        if !MANIFEST_NAME.is_match(&self.name) {
+        if !is_valid_name(&self.name) {
             eprintln!("Invalid name \"{}\" (only alphanmeric characters, '-' and '_')", self.name);
             return false;
         }
 
+        if let Some(ref author) = self.author {
+            if !is_valid_name(author) {
+                eprintln!("Invalid author \"{}\" (only alphanmeric characters, '-' and '_')", author);
+                return false;
+            }
+        }
+
+        for file in &self.files {
+            if !is_inside(file.src()) {
+                eprintln!("Invalid file \"{}\" (paths must be relative and stay inside the project)", file.src());
+                return false;
+            }
+
+            let target = match file {
+                PackageFile::Mapped { dst: Some(dst), .. } => dst,
+                _ => file.src(),
+            };
+            if !is_installable(target) {
+                eprintln!(
+                    "Invalid file \"{}\" (paths must be relative, stay inside the project and can't be hidden or project settings)",
+                    target
+                );
+                return false;
+            }
+        }
+
+        for (group, patterns) in &self.groups {
+            if let Some(pattern) = patterns.iter().find(|pattern| !is_installable(pattern)) {
+                eprintln!(
+                    "Invalid file \"{}\" in group {} (paths must be relative, stay inside the package and can't be hidden or project settings)",
+                    pattern, group
+                );
+                return false;
+            }
+        }
+
+        let registered = self.autoload.values().chain(self.editor_plugins.iter());
+        for file in registered {
+            if !self.files.iter().any(|f| f.src() == file) {
+                eprintln!("Invalid autoload or editor plugin \"{}\" (not one of the package files)", file);
+                return false;
+            }
+        }
+
+        for (action, input) in &self.input {
+            if let Some(key) = input.keys.iter().find(|key| godot::input::key_event(key).is_none()) {
+                eprintln!("Invalid key \"{}\" for input action {}", key, action);
+                return false;
+            }
+        }
+
         match self.version.parse::<f32>() {
             Ok(_) => {}
             Err(_) => {
@@ -126,14 +388,21 @@ impl Package {
 
     fn existing_files(&self, dst: &PathBuf) -> Vec<String> {
         let mut files = Vec::new();
-        for file in &self.files {
-            if dst.join(&file).exists() {
-                files.push(file.clone());
+        for target in self.targets(dst) {
+            if target.exists() {
+                files.push(target.display().to_string());
             }
         }
         files
     }
 
+    pub fn is_newer_than(&self, other: &Package) -> bool {
+        match (self.version.parse::<f32>(), other.version.parse::<f32>()) {
+            (Ok(this), Ok(other)) => this > other,
+            _ => false,
+        }
+    }
+
     /// Compare the remote version with the local version
     fn version_is_increased(&self, dst: &PathBuf) -> bool {
         match super::local_package(dst.join(MANIFEST)) {
@@ -159,48 +428,173 @@ impl Package {
     }
 }
 
-fn create_dir(dir: &PathBuf) -> Option<PathBuf> {
-    if let Some(parent_dir) = dir.parent() {
-        if create_dir_all(parent_dir).is_ok() {
-            return Some(parent_dir.into());
-        }
+/// Package and author names are alphanumeric, `-` and `_`
+pub fn is_valid_name(name: &str) -> bool {
+    MANIFEST_NAME.is_match(name)
+}
+
+/// Merge the local changes made to `path` into the newly installed version,
+/// keeping the local version as `.orig` if they conflict. Returns the
+/// number of conflicts
+fn merge_local_changes(path: &PathBuf, base: &str, local: &str) -> Result<usize> {
+    let upstream = read_to_string(path)?;
+    let merge = diff::merge3(base, local, &upstream);
+
+    if merge.conflicts > 0 {
+        let mut orig = path.clone().into_os_string();
+        orig.push(".orig");
+        File::create(orig)?.write_all(local.as_bytes())?;
     }
-    return None;
+    File::create(path)?.write_all(merge.text.as_bytes())?;
+
+    Ok(merge.conflicts)
 }
 
-fn copy_local(src: &PathBuf, dst: &PathBuf) -> Result<()> {
-    copy(src, dst)?;
+/// Write the manifest that was verified to the package directory
+fn write_manifest(manifest: &[u8], dst: &Path) -> Result<()> {
+    create_dir_all(dst)?;
+    File::create(dst.join(MANIFEST))?.write_all(manifest)?;
     Ok(())
 }
 
-fn copy_remote(root_url: &str, fragment: &str, dst: &PathBuf) -> Result<()> {
-    let complete_url = format!("{}{}", root_url, fragment);
+/// Fetch each file to a path in a scratch directory, checking it against
+/// the signature. Returns the fetched files
+fn fetch_files(fetcher: &Fetcher, src: &RootPath, files: Vec<(&str, PathBuf)>, signature: Option<&Signature>) -> Result<Vec<PathBuf>> {
+    let mut fetched = Vec::new();
+    fetcher.start_package();
+
+    for (file, dst_full) in files {
+        if let Some(parent) = dst_full.parent() {
+            create_dir_all(parent)?;
+        }
+
+        copy_file(fetcher, src, file, &dst_full)?;
+        if let Some(signature) = signature {
+            signature.check_file(file, &dst_full)?;
+        }
+        fetched.push(dst_full);
+    }
+
+    Ok(fetched)
+}
+
+/// Copy each staged file to its target. Files that already exist are backed
+/// up first, so if any copy fails they're restored, and only the files and
+/// directories created here are removed. Returns the installed files
+fn install_files(files: Vec<(PathBuf, PathBuf)>) -> Result<Vec<PathBuf>> {
+    let backup_dir = tempfile::Builder::new().prefix("gdt-backup-").tempdir()?;
+    let mut rollback = Rollback::default();
+    let mut installed = Vec::new();
+
+    for (staged, target) in files {
+        let res = rollback.prepare(&target, backup_dir.path()).and_then(|_| copy_local(&staged, &target));
+        if let Err(e) = res {
+            error!("Failed to copy file: {}, Rolling back", e);
+            rollback.restore();
+            return Err(e);
+        }
+        installed.push(target);
+    }
 
-    let mut response = reqwest::blocking::get(&complete_url)?;
+    Ok(installed)
+}
+
+/// What installing files changed, to undo it
+#[derive(Default)]
+struct Rollback {
+    /// Created directories, outermost first
+    dirs: Vec<PathBuf>,
+    /// Created files
+    files: Vec<PathBuf>,
+    /// Overwritten files and their backups
+    backups: Vec<(PathBuf, PathBuf)>,
+}
 
-    match response.status().as_u16() {
-        200..=299 => {}
-        status => {
-            if status == 404 {
-                return Err(crate::errors::Error::FileNotFound(fragment.to_string()));
-            } else {
-                return Err(crate::errors::Error::InvalidResponse);
+impl Rollback {
+    /// Create the missing directories of `target` and back it up if it exists
+    fn prepare(&mut self, target: &Path, backup_dir: &Path) -> Result<()> {
+        let mut missing = target
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .map(|dir| dir.to_path_buf())
+            .collect::<Vec<_>>();
+        missing.reverse();
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        self.dirs.extend(missing);
+
+        match target.exists() {
+            true => {
+                let backup = backup_dir.join(self.backups.len().to_string());
+                copy(target, &backup)?;
+                self.backups.push((target.to_path_buf(), backup));
             }
+            false => self.files.push(target.to_path_buf()),
         }
+        Ok(())
     }
 
-    let mut file = File::create(dst)?;
-    let _ = response.copy_to(&mut file);
+    fn restore(self) {
+        for file in &self.files {
+            let _ = remove_file(file);
+        }
+        for (target, backup) in &self.backups {
+            if let Err(e) = copy(backup, target) {
+                eprintln!("Failed to restore {}: {}", target.display(), e);
+            }
+        }
+        for dir in self.dirs.iter().rev() {
+            let _ = remove_dir(dir);
+        }
+    }
+}
 
-    Ok(())
+/// Whether a relative path stays inside the directory it's relative to
+fn is_inside(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty()
+        && path.components().all(|c| match c {
+            Component::Normal(_) | Component::CurDir => true,
+            _ => false,
+        })
 }
 
-fn rollback(paths: Vec<PathBuf>) {
-    paths.iter().filter(|file| file.is_file()).for_each(|file| {
-        let _ = remove_file(file);
+/// Whether a package may install a file at the relative `path`: it stays
+/// inside, has no hidden components such as `.git` or `.import` and isn't
+/// one of the project's settings files
+fn is_installable(path: &str) -> bool {
+    let hidden = Path::new(path).components().any(|c| match c {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
     });
+    let protected = Path::new(path)
+        .file_name()
+        .map(|name| PROTECTED_FILES.iter().any(|file| name == *file))
+        .unwrap_or(false);
 
-    paths.iter().filter(|dir| dir.is_dir()).for_each(|dir| {
-        let _ = remove_dir(dir);
-    });
+    is_inside(path) && !hidden && !protected
+}
+
+fn write_record(dst: &PathBuf, installed: &[PathBuf], mut record: InstallRecord) {
+    let res = record
+        .add_files(installed)
+        .and_then(|_| record.save(dst))
+        .and_then(|_| InstallRecord::save_base(dst, installed));
+    if let Err(e) = res {
+        eprintln!("Failed to write install record: {}", e);
+    }
+}
+
+fn copy_file(fetcher: &Fetcher, src: &RootPath, file: &str, dst: &PathBuf) -> Result<()> {
+    match src {
+        RootPath::Local(ref path) => copy_local(&path.join(file), dst),
+        RootPath::Remote(ref urls) => fetcher.download(urls, file, dst),
+    }
+}
+
+fn copy_local(src: &Path, dst: &Path) -> Result<()> {
+    copy(src, dst)?;
+    Ok(())
 }
@@ -0,0 +1,341 @@
+use std::path::Path;
+
+use crate::godot::{input, ConfigFile, ScriptClass, Variant, PROJECT_FILE};
+use crate::project::res_path;
+
+use super::package::Package;
+use super::record::InstallRecord;
+
+pub const AUTOLOAD: &'static str = "autoload";
+const EDITOR_PLUGINS: &'static str = "editor_plugins";
+const ENABLED_PLUGINS: &'static str = "enabled";
+/// Godot only loads editor plugins from `addons/<name>/plugin.cfg`
+const ADDONS_DIR: &'static str = "addons";
+const INPUT: &'static str = "input";
+const GLOBAL_CLASSES: &'static str = "_global_script_classes";
+const GLOBAL_CLASS_ICONS: &'static str = "_global_script_class_icons";
+
+/// Register the autoloads, editor plugins, input actions and `class_name`
+/// scripts of a package installed to `dst` in `project.godot`, replacing
+/// what an earlier version of it registered. What was added is kept in the
+/// install record so uninstall can remove it
+pub fn register(package: &Package, dst: &Path, previous: Option<&InstallRecord>, record: &mut InstallRecord) {
+    let path = Path::new(PROJECT_FILE);
+    if !path.exists() {
+        if !package.autoload.is_empty() || !package.editor_plugins.is_empty() || !package.input.is_empty() {
+            eprintln!("No {} in the current directory, {} has to be set up by hand", PROJECT_FILE, package.name());
+        }
+        return;
+    }
+
+    let mut project = match ConfigFile::load(path) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", PROJECT_FILE, e);
+            return;
+        }
+    };
+
+    if let Some(previous) = previous {
+        remove_entries(&mut project, previous);
+        for name in previous.input_actions.iter().filter(|name| !package.input.contains_key(*name)) {
+            project.remove(INPUT, name);
+        }
+    }
+
+    for (name, file) in &package.autoload {
+        let target = match package.target_of(file, dst) {
+            Some(t) => t,
+            None => continue,
+        };
+
+        let value = format!("*{}", res_path(&target));
+        match project.get_raw(AUTOLOAD, name) {
+            Some(existing) if Variant::parse(existing).ok() != Some(Variant::String(value.clone())) => {
+                eprintln!("Not adding autoload {}, the project already has one called {}", name, name);
+                continue;
+            }
+            Some(_) => {}
+            None => project.set(AUTOLOAD, name, &Variant::String(value)),
+        }
+        println!("Added autoload: {}", name);
+        record.autoloads.push(name.clone());
+    }
+
+    let mut enabled = enabled_plugins(&project);
+    for file in &package.editor_plugins {
+        let plugin = match package.target_of(file, dst).and_then(|target| plugin_name(&target)) {
+            Some(p) => p,
+            None => {
+                eprintln!("Not enabling editor plugin {}, it has to be installed to {}/<name>/plugin.cfg", file, ADDONS_DIR);
+                continue;
+            }
+        };
+
+        // Enabled by the user, leave it enabled on uninstall
+        let is_enabled = enabled.iter().any(|entry| entry_plugin(entry).as_ref() == Some(&plugin));
+        if is_enabled && !previous.map(|p| p.editor_plugins.contains(&plugin)).unwrap_or(false) {
+            continue;
+        }
+        if !is_enabled {
+            let entry = plugin_entry(&plugin, &enabled);
+            enabled.push(entry);
+        }
+        println!("Enabled editor plugin: {}", plugin);
+        record.editor_plugins.push(plugin);
+    }
+    set_enabled_plugins(&mut project, enabled);
+
+    let mut collisions = Vec::new();
+    for (name, action) in &package.input {
+        if project.get_raw(INPUT, name).is_some() {
+            match previous.map(|p| p.input_actions.contains(name)).unwrap_or(false) {
+                // Added by an earlier version, the user may have remapped it since
+                true => record.input_actions.push(name.clone()),
+                false => collisions.push(name.as_str()),
+            }
+            continue;
+        }
+
+        let mut events = action.keys.iter().filter_map(|key| input::key_event(key)).collect::<Vec<_>>();
+        events.extend(action.buttons.iter().map(|&button| input::joypad_button_event(button)));
+        project.set(INPUT, name, &input::action(action.deadzone, events));
+        println!("Added input action: {}", name);
+        record.input_actions.push(name.clone());
+    }
+
+    if !collisions.is_empty() {
+        eprintln!("Input actions already defined in the project, kept as they are: {}", collisions.join(", "));
+    }
+
+    let removed = previous.map(|p| recorded_scripts(p)).unwrap_or_default();
+    let added = package
+        .targets(dst)
+        .iter()
+        .filter(|target| target.extension().map(|ext| ext == "gd").unwrap_or(false))
+        .map(|target| res_path(target))
+        .collect::<Vec<_>>();
+    update_classes(&mut project, &removed, &added);
+
+    if let Err(e) = project.save(path) {
+        eprintln!("Failed to write {}: {}", PROJECT_FILE, e);
+    }
+}
+
+/// Remove the autoloads, editor plugins, input actions and classes a package registered
+pub fn unregister(record: &InstallRecord) {
+    let path = Path::new(PROJECT_FILE);
+    let scripts = recorded_scripts(record);
+    if record.autoloads.is_empty() && record.editor_plugins.is_empty() && record.input_actions.is_empty() && scripts.is_empty() {
+        return;
+    }
+
+    let mut project = match ConfigFile::load(path) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", PROJECT_FILE, e);
+            return;
+        }
+    };
+
+    remove_entries(&mut project, record);
+    for name in &record.input_actions {
+        project.remove(INPUT, name);
+    }
+    update_classes(&mut project, &scripts, &[]);
+
+    if let Err(e) = project.save(path) {
+        eprintln!("Failed to write {}: {}", PROJECT_FILE, e);
+    }
+}
+
+/// The reference to `path` on `line` of `project.godot` is one of the
+/// settings `record` registered: its autoloads, editor plugins, or the
+/// classes and icons of its scripts
+pub fn is_registered(project: &ConfigFile, record: &InstallRecord, line: usize, path: &str) -> bool {
+    match project.setting_at(line) {
+        Some((AUTOLOAD, name)) => record.autoloads.iter().any(|autoload| autoload == name),
+        Some((EDITOR_PLUGINS, ENABLED_PLUGINS)) => {
+            entry_plugin(path).map(|plugin| record.editor_plugins.contains(&plugin)).unwrap_or(false)
+        }
+        Some(("", GLOBAL_CLASSES)) => recorded_scripts(record).iter().any(|script| script == path),
+        Some(("", GLOBAL_CLASS_ICONS)) => {
+            let scripts = recorded_scripts(record);
+            global_classes(project).iter().any(|class| class.icon == path && scripts.contains(&class.path))
+        }
+        _ => false,
+    }
+}
+
+/// Remove the autoloads and editor plugins in `record`
+fn remove_entries(project: &mut ConfigFile, record: &InstallRecord) {
+    for name in &record.autoloads {
+        project.remove(AUTOLOAD, name);
+    }
+
+    if !record.editor_plugins.is_empty() {
+        let enabled = enabled_plugins(project)
+            .into_iter()
+            .filter(|entry| !entry_plugin(entry).map(|p| record.editor_plugins.contains(&p)).unwrap_or(false))
+            .collect();
+        set_enabled_plugins(project, enabled);
+    }
+}
+
+/// Replace the classes of the `removed` scripts in `_global_script_classes`
+/// by those declared in the `added` scripts, keeping their icons in
+/// `_global_script_class_icons` in step. Godot only updates these when the
+/// editor rescans the project
+fn update_classes(project: &mut ConfigFile, removed: &[String], added: &[String]) {
+    let current = global_classes(project);
+    let mut classes = current
+        .iter()
+        .filter(|class| !removed.contains(&class.path) && !added.contains(&class.path))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for mut class in added.iter().filter_map(|path| ScriptClass::load(path)) {
+        if let Some(existing) = classes.iter().find(|c| c.name == class.name) {
+            eprintln!("Not registering class {} of {}, {} already declares it", class.name, class.path, existing.path);
+            continue;
+        }
+        // Keep what the editor wrote for the script that gdt doesn't know about
+        if let Some(previous) = current.iter().find(|c| c.path == class.path) {
+            class.other = previous.other.clone();
+        }
+        if !current.contains(&class) {
+            println!("Registered class: {}", class.name);
+        }
+        classes.push(class);
+    }
+
+    for class in current.iter().filter(|class| !classes.iter().any(|c| c.name == class.name)) {
+        println!("Unregistered class: {}", class.name);
+    }
+
+    if classes == current {
+        return;
+    }
+
+    classes.sort_by(|a, b| a.name.cmp(&b.name));
+    let icons = classes
+        .iter()
+        .map(|class| (Variant::String(class.name.clone()), Variant::String(class.icon.clone())))
+        .collect();
+    project.set("", GLOBAL_CLASSES, &Variant::Array(classes.iter().map(|class| class.to_variant()).collect()));
+    project.set("", GLOBAL_CLASS_ICONS, &Variant::Dictionary(icons));
+}
+
+fn global_classes(project: &ConfigFile) -> Vec<ScriptClass> {
+    let classes = match project.get("", GLOBAL_CLASSES) {
+        Ok(Some(Variant::Array(classes))) => classes,
+        _ => return Vec::new(),
+    };
+    let icons = project.get("", GLOBAL_CLASS_ICONS).ok().and_then(|icons| icons);
+
+    classes
+        .iter()
+        .filter_map(ScriptClass::from_variant)
+        .map(|mut class| {
+            class.icon = icons
+                .as_ref()
+                .and_then(|icons| icons.get(&class.name))
+                .and_then(|icon| icon.as_str())
+                .unwrap_or_default()
+                .to_string();
+            class
+        })
+        .collect()
+}
+
+/// The `res://` paths of the scripts a package installed
+fn recorded_scripts(record: &InstallRecord) -> Vec<String> {
+    record.files.keys().filter(|file| file.ends_with(".gd")).map(|file| format!("res://{}", file)).collect()
+}
+
+/// `addons/<name>/plugin.cfg` is enabled as `<name>`
+fn plugin_name(plugin_cfg: &Path) -> Option<String> {
+    let parts = plugin_cfg.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>();
+    match parts.as_slice() {
+        [addons, name, cfg] if addons == ADDONS_DIR && cfg == "plugin.cfg" => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// The plugin an entry of the enabled plugins is for. Godot 3.0 and 3.1 list
+/// plugins by name, later versions by the path of their `plugin.cfg`
+fn entry_plugin(entry: &str) -> Option<String> {
+    match entry.starts_with("res://") {
+        true => plugin_name(Path::new(entry.trim_start_matches("res://"))),
+        false => Some(entry.to_string()),
+    }
+}
+
+/// The entry enabling `plugin`, in the format of the `enabled` entries,
+/// by the path of its `plugin.cfg` unless they're all names
+fn plugin_entry(plugin: &str, enabled: &[String]) -> String {
+    match !enabled.is_empty() && enabled.iter().all(|entry| !entry.starts_with("res://")) {
+        true => plugin.to_string(),
+        false => format!("res://{}/{}/plugin.cfg", ADDONS_DIR, plugin),
+    }
+}
+
+/// The entries of the enabled plugins as they're written
+fn enabled_plugins(project: &ConfigFile) -> Vec<String> {
+    match project.get(EDITOR_PLUGINS, ENABLED_PLUGINS) {
+        Ok(Some(Variant::Constructor { args, .. })) | Ok(Some(Variant::Array(args))) => {
+            args.into_iter().filter_map(|arg| arg.as_str().map(|s| s.to_string())).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn set_enabled_plugins(project: &mut ConfigFile, enabled: Vec<String>) {
+    if enabled == enabled_plugins(project) {
+        return;
+    }
+    if enabled.is_empty() {
+        project.remove(EDITOR_PLUGINS, ENABLED_PLUGINS);
+        return;
+    }
+
+    let value = Variant::Constructor {
+        name: "PoolStringArray".to_string(),
+        args: enabled.into_iter().map(Variant::String).collect(),
+    };
+    project.set(EDITOR_PLUGINS, ENABLED_PLUGINS, &value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &'static str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/project.godot"));
+
+    #[test]
+    fn plugins_are_enabled_by_name_or_path() {
+        let project = ConfigFile::parse(PROJECT);
+        let enabled = enabled_plugins(&project);
+        assert_eq!(enabled, vec!["res://addons/tool/plugin.cfg"]);
+        assert_eq!(entry_plugin(&enabled[0]), Some("tool".to_string()));
+        assert_eq!(entry_plugin("tool"), Some("tool".to_string()));
+
+        assert_eq!(plugin_entry("menu", &enabled), "res://addons/menu/plugin.cfg");
+        assert_eq!(plugin_entry("menu", &[]), "res://addons/menu/plugin.cfg");
+        assert_eq!(plugin_entry("menu", &["tool".to_string()]), "menu");
+    }
+
+    #[test]
+    fn uninstall_removes_plugins_enabled_by_path() {
+        let mut project = ConfigFile::parse(PROJECT);
+        let record = InstallRecord {
+            editor_plugins: vec!["tool".to_string()],
+            ..Default::default()
+        };
+        assert!(is_registered(&project, &record, 38, "res://addons/tool/plugin.cfg"));
+
+        remove_entries(&mut project, &record);
+        assert_eq!(project.get_raw(EDITOR_PLUGINS, ENABLED_PLUGINS), None);
+        assert!(!project.to_string().contains("[editor_plugins]"));
+    }
+}
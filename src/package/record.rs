@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read, read_dir, read_to_string, remove_dir, remove_dir_all, remove_file, rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::Result;
+use crate::hash::hash_file;
+use crate::remote::Fetcher;
+
+use super::package::{RootPath, MANIFEST};
+use super::signing::SIGNATURE;
+
+/// Metadata directory inside an installed package
+pub const META_DIR: &'static str = ".gdt";
+const RECORD: &'static str = "record.toml";
+/// Pristine copies of the installed text files, the base for merging local changes
+const BASE_DIR: &'static str = "base";
+
+lazy_static! {
+    static ref GITHUB_RAW: Regex = Regex::new(r"^https://raw\.githubusercontent\.com/([^/]+)/([^/]+)/([^/]+)/").unwrap();
+}
+
+/// The part of the GitHub commits api response that's needed
+#[derive(Deserialize)]
+struct GithubCommit {
+    sha: String,
+}
+
+/// Written to `pack/<name>/.gdt/record.toml` on install, keeps track of
+/// where the package came from and the files that were installed
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InstallRecord {
+    /// The package path or url as it was given to install
+    pub source: Option<String>,
+    /// The path or base url the package was fetched from
+    pub resolved: Option<String>,
+    /// The commit of the source, for git repositories and GitHub sources
+    pub commit: Option<String>,
+    /// Seconds since the unix epoch
+    pub installed_at: Option<u64>,
+    pub gdt_version: Option<String>,
+    /// Optional groups installed with `--with`
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Autoload singletons added to `project.godot`
+    #[serde(default)]
+    pub autoloads: Vec<String>,
+    /// Editor plugins enabled in `project.godot`
+    #[serde(default)]
+    pub editor_plugins: Vec<String>,
+    /// Input map actions added to `project.godot`
+    #[serde(default)]
+    pub input_actions: Vec<String>,
+    /// Installed files, relative to the project root, and their hashes
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+    pub added: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.deleted.is_empty() && self.added.is_empty()
+    }
+}
+
+impl InstallRecord {
+    pub fn new(fetcher: &Fetcher, source: &str, src: &RootPath) -> Self {
+        let resolved = match src {
+            RootPath::Local(path) => path.canonicalize().unwrap_or(path.clone()).to_string_lossy().to_string(),
+            RootPath::Remote(urls) => urls[0].clone(),
+        };
+
+        Self {
+            source: Some(source.to_string()),
+            resolved: Some(resolved),
+            commit: source_commit(fetcher, src),
+            installed_at: SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs()),
+            gdt_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            groups: Vec::new(),
+            autoloads: Vec::new(),
+            editor_plugins: Vec::new(),
+            input_actions: Vec::new(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn load(dst: &Path) -> Result<Self> {
+        let data = read_to_string(dst.join(META_DIR).join(RECORD))?;
+        Ok(toml::from_str(&data)?)
+    }
+
+    pub fn exists(dst: &Path) -> bool {
+        dst.join(META_DIR).join(RECORD).exists()
+    }
+
+    pub fn save(&self, dst: &Path) -> Result<()> {
+        create_dir_all(dst.join(META_DIR))?;
+        let mut file = File::create(dst.join(META_DIR).join(RECORD))?;
+        file.write_all(toml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Record the current hash of every installed file
+    pub fn add_files(&mut self, files: &[PathBuf]) -> Result<()> {
+        for file in files {
+            self.files.insert(project_path(file), hash_file(file)?);
+        }
+        Ok(())
+    }
+
+    /// Keep a copy of every installed text file as the base for merging
+    /// local changes on update
+    pub fn save_base(dst: &Path, files: &[PathBuf]) -> Result<()> {
+        let base_dir = dst.join(META_DIR).join(BASE_DIR);
+        if base_dir.exists() {
+            remove_dir_all(&base_dir)?;
+        }
+
+        for file in files {
+            let data = read(file)?;
+            if std::str::from_utf8(&data).is_err() {
+                continue;
+            }
+
+            let base = base_dir.join(project_path(file));
+            if let Some(parent) = base.parent() {
+                create_dir_all(parent)?;
+            }
+            File::create(base)?.write_all(&data)?;
+        }
+        Ok(())
+    }
+
+    /// The file as it was installed, if it's a text file
+    pub fn base_text(dst: &Path, file: &Path) -> Option<String> {
+        read_to_string(dst.join(META_DIR).join(BASE_DIR).join(project_path(file))).ok()
+    }
+
+    /// Update the recorded files after the files below `from` were moved to
+    /// `to`, moving their base copies in the package directory `dst` along
+    pub fn move_files(&mut self, dst: &Path, from: &Path, to: &Path) -> Result<()> {
+        let from = format!("{}/", project_path(from));
+        let to = format!("{}/", project_path(to));
+
+        let mut moved = Vec::new();
+        let files = std::mem::replace(&mut self.files, BTreeMap::new());
+        for (file, hash) in files {
+            match file.starts_with(&from) {
+                true => {
+                    let new_file = format!("{}{}", to, &file[from.len()..]);
+                    self.files.insert(new_file.clone(), hash);
+                    moved.push((file, new_file));
+                }
+                false => {
+                    self.files.insert(file, hash);
+                }
+            }
+        }
+
+        let base_dir = dst.join(META_DIR).join(BASE_DIR);
+        for (file, new_file) in moved {
+            let base = base_dir.join(&file);
+            if !base.exists() {
+                continue;
+            }
+
+            let new_base = base_dir.join(&new_file);
+            if let Some(parent) = new_base.parent() {
+                create_dir_all(parent)?;
+            }
+            rename(&base, &new_base)?;
+
+            // Remove the directories the base copy leaves empty
+            let mut dir = base.parent();
+            while let Some(d) = dir {
+                if d == base_dir || remove_dir(d).is_err() {
+                    break;
+                }
+                dir = d.parent();
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the installed files, and any directories left empty
+    pub fn remove_files(&self) {
+        for file in self.files.keys() {
+            remove_installed(file);
+        }
+    }
+
+    /// Remove the recorded files that aren't `kept`, such as the files of an
+    /// older version that the new one no longer has. Files changed since they
+    /// were installed are left in place and returned with their recorded hash
+    pub fn remove_stale(&self, kept: &[PathBuf]) -> BTreeMap<String, String> {
+        let kept = kept.iter().map(|path| project_path(path)).collect::<Vec<_>>();
+        let mut modified = BTreeMap::new();
+        for (file, hash) in self.files.iter().filter(|(file, _)| !kept.contains(file)) {
+            let path = PathBuf::from(file);
+            match (path.exists(), self.is_modified(&path)) {
+                (false, _) => {}
+                (true, true) => {
+                    modified.insert(file.clone(), hash.clone());
+                }
+                (true, false) => remove_installed(file),
+            }
+        }
+        modified
+    }
+
+    /// Whether an installed file was changed since it was installed
+    pub fn is_modified(&self, file: &Path) -> bool {
+        match self.files.get(&project_path(file)) {
+            Some(hash) => file.exists() && hash_file(file).map(|h| h != *hash).unwrap_or(true),
+            None => false,
+        }
+    }
+
+    /// Compare the recorded files with what is on disk, files found in the
+    /// package directory that were never installed are reported as added
+    pub fn changes(&self, dst: &Path) -> Changes {
+        let mut changes = Changes::default();
+
+        for (file, hash) in &self.files {
+            let path = PathBuf::from(file);
+            match hash_file(&path) {
+                Ok(ref current) if current == hash => {}
+                Ok(_) => changes.modified.push(file.clone()),
+                Err(_) => changes.deleted.push(file.clone()),
+            }
+        }
+
+        let mut on_disk = Vec::new();
+        walk(dst, &mut on_disk);
+        for path in on_disk {
+            let file = project_path(&path);
+            let is_meta = path.strip_prefix(dst).map(|p| p == Path::new(MANIFEST) || p == Path::new(SIGNATURE)).unwrap_or(false);
+            if !is_meta && !self.files.contains_key(&file) {
+                changes.added.push(file);
+            }
+        }
+
+        changes
+    }
+}
+
+/// Remove an installed file and any directories it leaves empty
+fn remove_installed(file: &str) {
+    let path = PathBuf::from(file);
+    if let Err(e) = remove_file(&path) {
+        eprintln!("Failed to remove {}: {}", file, e);
+        return;
+    }
+
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if d.as_os_str().is_empty() || remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+/// The current commit of a local git repository, or of the branch
+/// or tag of a GitHub source
+fn source_commit(fetcher: &Fetcher, src: &RootPath) -> Option<String> {
+    match src {
+        RootPath::Local(path) => {
+            let output = Command::new("git").arg("-C").arg(path).args(&["rev-parse", "HEAD"]).output().ok()?;
+            match output.status.success() {
+                true => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+                false => None,
+            }
+        }
+        // Only GitHub has an api to look up the commit, other hosts aren't asked
+        RootPath::Remote(urls) => {
+            let caps = GITHUB_RAW.captures(&urls[0])?;
+            let api = format!("https://api.github.com/repos/{}/{}/commits/", &caps[1], &caps[2]);
+            let response = fetcher.text(&[api], &caps[3]).ok()?;
+            serde_json::from_str::<GithubCommit>(&response).ok().map(|commit| commit.sha)
+        }
+    }
+}
+
+/// All files below `dir`, skipping the metadata directory
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != META_DIR {
+                walk(&path, files);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// A path relative to the project root, with `/` as separator
+pub fn project_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_files_are_removed_unless_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.path().join(name);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(&path).unwrap().write_all(text.as_bytes()).unwrap();
+            path
+        };
+        let kept = write("pack/menu/menu.gd", "extends Node\n");
+        let old = write("addons/menu/old.gd", "extends Node\n");
+        let changed = write("pack/menu/changed.gd", "extends Node\n");
+
+        let mut record = InstallRecord::default();
+        record.add_files(&[kept.clone(), old.clone(), changed.clone()]).unwrap();
+        write("pack/menu/changed.gd", "extends Control\n");
+
+        let modified = record.remove_stale(&[kept.clone()]);
+        assert!(kept.exists());
+        assert!(!old.exists());
+        assert!(!dir.path().join("addons").exists());
+        assert!(changed.exists());
+        assert_eq!(modified.keys().cloned().collect::<Vec<_>>(), vec![project_path(&changed)]);
+        assert_eq!(modified.get(&project_path(&changed)), record.files.get(&project_path(&changed)));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::project::{references_at, res_path, REFERENCING_EXTENSIONS};
+
+use super::groups;
+use super::install_dir;
+use super::package::Package;
+
+/// A reference in a package file to a file that isn't part of the package
+pub struct Dangling {
+    /// The referencing file, relative to the package directory
+    pub file: String,
+    pub line: usize,
+    pub path: String,
+}
+
+/// The references in the scenes, resources and scripts of the package in
+/// `dir` to files that won't be installed with it. `res://` paths are
+/// checked against where the files are installed, with either layout.
+/// Paths outside the project, such as `user://`, and the package's
+/// `external` paths are allowed. Files that can't be read are reported
+pub fn dangling(package: &Package, dir: &Path) -> Option<Vec<Dangling>> {
+    let layouts = vec![install_dir(package, false), install_dir(package, true)]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let installed = layouts
+        .iter()
+        .flat_map(|dst| package.files.iter().map(move |file| res_path(&file.target(dst))))
+        .collect::<BTreeSet<_>>();
+
+    let mut dangling = Vec::new();
+    for file in &package.files {
+        let source = dir.join(file.src());
+        let referencing = source
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| REFERENCING_EXTENSIONS.contains(&ext))
+            .unwrap_or(false);
+        if !referencing || !source.is_file() {
+            continue;
+        }
+
+        let location = res_path(&file.target(&layouts[0]));
+        let references = match references_at(&source, &location) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Failed to read references of {}: {}", file.src(), e);
+                return None;
+            }
+        };
+
+        for reference in references {
+            let allowed = !reference.path.starts_with("res://")
+                || installed.contains(&reference.path)
+                || package.external.iter().any(|pattern| groups::matches(pattern, &reference.path));
+
+            if !allowed {
+                dangling.push(Dangling {
+                    file: file.src().to_string(),
+                    line: reference.line,
+                    path: reference.path,
+                });
+            }
+        }
+    }
+    Some(dangling)
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read, read_to_string, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+use data_encoding::BASE64;
+use log::info;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::config_dir;
+use crate::errors::{Error, Result};
+use crate::hash::{hash_file, sha256};
+use crate::keys;
+use crate::remote::Fetcher;
+
+use super::groups;
+use super::package::{Package, RootPath, MANIFEST};
+
+pub const SIGNATURE: &'static str = "signature.toml";
+const SIGNING_KEY: &'static str = "signing_key.pk8";
+
+/// `signature.toml`, an Ed25519 signature over the hash of the
+/// manifest and the hashes of every file in the package
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Signature {
+    /// Base64 encoded public key
+    pub key: String,
+    /// Base64 encoded signature
+    pub signature: String,
+    pub manifest: String,
+    pub files: BTreeMap<String, String>,
+}
+
+impl Signature {
+    /// The signed message, one `<hash> <path>` line per file
+    /// starting with the manifest
+    fn message(manifest: &str, files: &BTreeMap<String, String>) -> Vec<u8> {
+        let mut message = format!("{} {}\n", manifest, MANIFEST);
+        for (file, hash) in files {
+            message.push_str(&format!("{} {}\n", hash, file));
+        }
+        message.into_bytes()
+    }
+
+    /// Check the signature itself, returning the public key it was made with
+    fn check(&self) -> Result<Vec<u8>> {
+        let key = BASE64.decode(self.key.as_bytes()).map_err(|_| Error::InvalidSignature)?;
+        let signature = BASE64.decode(self.signature.as_bytes()).map_err(|_| Error::InvalidSignature)?;
+
+        UnparsedPublicKey::new(&ED25519, &key)
+            .verify(&Self::message(&self.manifest, &self.files), &signature)
+            .map_err(|_| Error::InvalidSignature)?;
+
+        Ok(key)
+    }
+
+    /// Check a downloaded file against its signed hash
+    pub fn check_file(&self, file: &str, path: &PathBuf) -> Result<()> {
+        match self.files.get(file) {
+            Some(hash) if *hash == hash_file(path)? => Ok(()),
+            _ => Err(Error::HashMismatch(file.to_string())),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Signing -
+// -----------------------------------------------------------------------------
+pub fn sign(dir: PathBuf, key: Option<PathBuf>) {
+    let key_pair = match signing_key(key) {
+        Ok(k) => k,
+        Err(e) => {
+            eprintln!("Failed to load signing key: {}", e);
+            return;
+        }
+    };
+
+    match create_signature(&dir, &key_pair) {
+        Ok(signature) => println!("Signed {:?} with key {}", dir, signature.key),
+        Err(e) => eprintln!("Failed to sign {:?}: {}", dir, e),
+    }
+}
+
+fn create_signature(dir: &PathBuf, key_pair: &Ed25519KeyPair) -> Result<Signature> {
+    let manifest_data = read(dir.join(MANIFEST))?;
+    let package = toml::from_slice::<Package>(&manifest_data)?;
+
+    let mut files = BTreeMap::new();
+    for file in &package.files {
+        files.insert(file.src().to_string(), hash_file(dir.join(file.src()))?);
+    }
+
+    // Optional groups are signed too, so they can be installed from remote sources
+    let available = groups::local_files(dir);
+    for patterns in package.groups.values() {
+        for file in groups::expand(patterns, Some(&available))? {
+            let hash = hash_file(dir.join(&file))?;
+            files.insert(file, hash);
+        }
+    }
+
+    let manifest = sha256(&manifest_data);
+    let signature = key_pair.sign(&Signature::message(&manifest, &files));
+    let signature = Signature {
+        key: BASE64.encode(key_pair.public_key().as_ref()),
+        signature: BASE64.encode(signature.as_ref()),
+        manifest,
+        files,
+    };
+
+    let mut file = File::create(dir.join(SIGNATURE))?;
+    file.write_all(toml::to_string(&signature)?.as_bytes())?;
+
+    Ok(signature)
+}
+
+/// Load the signing key, generating the default key on first use
+fn signing_key(path: Option<PathBuf>) -> Result<Ed25519KeyPair> {
+    let path = match path {
+        Some(p) => p,
+        None => {
+            let default = config_dir().join(SIGNING_KEY);
+            if !default.exists() {
+                generate_key(&default)?;
+            }
+            default
+        }
+    };
+
+    Ed25519KeyPair::from_pkcs8(&read(path)?).map_err(|_| Error::InvalidKey)
+}
+
+/// Write a new key readable only by the user, an existing key is never overwritten
+fn generate_key(path: &PathBuf) -> Result<()> {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|_| Error::InvalidKey)?;
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(|_| Error::InvalidKey)?;
+
+    create_dir_all(config_dir())?;
+    let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+    file.write_all(pkcs8.as_ref())?;
+
+    println!("Generated a new signing key: {:?}", path);
+    println!("Public key: {}", BASE64.encode(key_pair.public_key().as_ref()));
+    println!("Share the public key so others can trust it with `gdt keys add <name> <key>`");
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+//     - Verification -
+// -----------------------------------------------------------------------------
+/// Verify the signature of a package before installing it.
+///
+/// A tampered package is always refused. Unsigned packages and packages
+/// signed with a key that is not in the trusted keys store are refused in
+/// strict mode, otherwise they only produce a warning.
+///
+/// `manifest` is the manifest as it was read, the same bytes that are installed
+pub fn verify(fetcher: &Fetcher, src: &RootPath, package: &Package, manifest: &[u8], strict: bool) -> Result<Option<Signature>> {
+    let signature = match src {
+        RootPath::Local(path) => {
+            let signature_path = path.join(SIGNATURE);
+            match signature_path.exists() {
+                true => Some(read_to_string(signature_path)?),
+                false => None,
+            }
+        }
+        RootPath::Remote(urls) => match fetcher.text(urls, SIGNATURE) {
+            Ok(signature) => Some(signature),
+            Err(Error::Http { status: 404, .. }) => None,
+            Err(e) => return Err(e),
+        },
+    };
+
+    let signature = match signature {
+        Some(s) => toml::from_str::<Signature>(&s)?,
+        None if strict => return Err(Error::Unsigned(package.name().to_string())),
+        None => {
+            eprintln!("Warning: {} is not signed", package.name());
+            return Ok(None);
+        }
+    };
+
+    let key = signature.check()?;
+    if signature.manifest != sha256(manifest) {
+        return Err(Error::HashMismatch(MANIFEST.to_string()));
+    }
+
+    match keys::find_trusted(&key) {
+        Some(name) => info!("{} signed by trusted key {}", package.name(), name),
+        None if strict => return Err(Error::UntrustedKey(signature.key)),
+        None => eprintln!("Warning: {} is signed by an untrusted key {}", package.name(), signature.key),
+    }
+
+    Ok(Some(signature))
+}
@@ -0,0 +1,699 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env::current_dir;
+use std::fs::{create_dir_all, read_dir, read_to_string, rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+use crate::errors::{Error, Result};
+use crate::godot::TextResource;
+
+/// Files that can reference other resources by `res://` path
+pub const REFERENCING_EXTENSIONS: [&'static str; 6] = ["tscn", "tres", "gd", "gdns", "gdnlib", "godot"];
+
+/// Godot's import settings for a file are kept next to it as `<file>.import`
+const IMPORT_EXT: &'static str = "import";
+
+/// Files in Godot's text resource format, their references are `ext_resource`s
+pub const TEXT_RESOURCE_EXTENSIONS: [&'static str; 3] = ["tscn", "tres", "gdns"];
+
+lazy_static! {
+    /// `preload("...")` and `load("...")` of a literal path in GDScript
+    static ref SCRIPT_LOAD: Regex = Regex::new(r#"\b(?:pre)?load\s*\(\s*"([^"]+)"\s*\)"#).unwrap();
+    /// `extends "..."` of a script by its path, in either quotes
+    static ref SCRIPT_EXTENDS: Regex = Regex::new(r#"^\s*extends\s+(?:"([^"]+)"|'([^']+)')"#).unwrap();
+    /// Any `"res://..."` string, for the other referencing files. Autoloads
+    /// are written as `"*res://..."`
+    static ref RES_STRING: Regex = Regex::new(r#""\*?(res://[^"]+)""#).unwrap();
+    /// The `path` of an `ext_resource` heading
+    static ref EXT_RESOURCE_PATH: Regex = Regex::new(r#"^\[ext_resource\b.*?\bpath="([^"]+)""#).unwrap();
+}
+
+/// Every file in the project below `root` with one of `extensions`,
+/// skipping hidden directories such as `.git` and `.import`
+pub fn project_files(root: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    walk(root, extensions, &mut files);
+    files.sort();
+    files
+}
+
+fn walk(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) {
+    let entries = match read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            walk(&path, extensions, files);
+        } else if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            if extensions.contains(&ext) {
+                files.push(path);
+            }
+        }
+    }
+}
+
+/// The `res://` path of a file relative to the project root
+pub fn res_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    let parts = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    format!("res://{}", parts.join("/"))
+}
+
+/// The file of a `res://` path, relative to the project root
+pub fn res_file(res_path: &str) -> PathBuf {
+    Path::new(".").join(res_path.trim_start_matches("res://"))
+}
+
+/// A `res://` path or a path relative to the current directory,
+/// which should be the project root, as a `res://` path
+pub fn to_res_path(path: &str) -> String {
+    if path.starts_with("res://") {
+        return path.to_string();
+    }
+
+    let mut path = PathBuf::from(path);
+    if let Ok(cwd) = current_dir() {
+        if let Ok(relative) = path.strip_prefix(&cwd) {
+            path = relative.to_path_buf();
+        }
+    }
+    res_path(&path)
+}
+
+/// Resolve a path written in the file at `res_path`, relative paths
+/// are relative to its directory. Other schemes, such as `user://`,
+/// are left as they are
+pub fn resolve_res_path(res_path: &str, path: &str) -> String {
+    if path.contains("://") {
+        return path.to_string();
+    }
+
+    let dir = res_path.rsplitn(2, '/').nth(1).unwrap_or("res:/");
+    let mut parts = dir.trim_start_matches("res://").split('/').filter(|p| !p.is_empty()).collect::<Vec<_>>();
+    for part in path.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("res://{}", parts.join("/"))
+}
+
+/// The path of `target` relative to the directory of the file at `res_path`,
+/// both `res://` paths
+pub fn relative_res_path(res_path: &str, target: &str) -> String {
+    let dir = res_path.trim_start_matches("res://").split('/').collect::<Vec<_>>();
+    let dir = &dir[..dir.len() - 1];
+    let target = target.trim_start_matches("res://").split('/').collect::<Vec<_>>();
+
+    let common = dir.iter().zip(target.iter()).take_while(|(a, b)| a == b).count();
+    let mut parts = vec![".."; dir.len() - common];
+    parts.extend(&target[common..]);
+    parts.join("/")
+}
+
+/// A reference to another file, by its `res://` path
+pub struct Reference {
+    pub path: String,
+    pub line: usize,
+}
+
+/// The files `file` references directly: the `ext_resource`s of scenes and
+/// resources, the paths scripts `preload`, `load` or `extends`, and any
+/// `res://` path in the other referencing files
+pub fn references(file: &Path) -> Result<Vec<Reference>> {
+    references_at(file, &res_path(file))
+}
+
+/// The files `file` references directly, with relative paths resolved
+/// as if it was at `res`
+pub fn references_at(file: &Path, res: &str) -> Result<Vec<Reference>> {
+    let extension = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    if TEXT_RESOURCE_EXTENSIONS.contains(&extension) {
+        let resource = TextResource::load(file)?;
+        if resource.header().is_none() {
+            return Err(Error::Parse("expected a gd_scene or gd_resource heading".to_string()));
+        }
+        return Ok(resource
+            .ext_resources()
+            .iter()
+            .map(|ext| Reference {
+                path: resolve_res_path(res, ext.path),
+                line: ext.line,
+            })
+            .collect());
+    }
+
+    let (patterns, comment) = match extension {
+        "gd" => (vec![&*SCRIPT_EXTENDS, &*SCRIPT_LOAD], "#"),
+        _ => (vec![&*RES_STRING], ";"),
+    };
+
+    let text = read_to_string(file)?;
+    let mut references = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with(comment) {
+            continue;
+        }
+        for caps in patterns.iter().flat_map(|pattern| pattern.captures_iter(line)) {
+            let path = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            references.push(Reference {
+                path: resolve_res_path(res, path),
+                line: n + 1,
+            });
+        }
+    }
+    Ok(references)
+}
+
+/// The references of every referencing file in the project
+pub struct DependencyGraph {
+    references: BTreeMap<String, Vec<Reference>>,
+}
+
+impl DependencyGraph {
+    /// Files that can't be parsed are reported and left out
+    pub fn build(root: &Path) -> Self {
+        let mut references = BTreeMap::new();
+        for file in project_files(root, &REFERENCING_EXTENSIONS) {
+            match self::references(&file) {
+                Ok(r) => {
+                    references.insert(res_path(&file), r);
+                }
+                Err(e) => eprintln!("Failed to read references of {}: {}", file.display(), e),
+            }
+        }
+        Self { references }
+    }
+
+    /// Every file `file` needs, directly or through other files
+    pub fn dependencies(&self, file: &str) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let mut pending = vec![file.to_string()];
+
+        while let Some(next) = pending.pop() {
+            for reference in self.references.get(&next).map(|r| r.as_slice()).unwrap_or(&[]) {
+                if reference.path != file && found.insert(reference.path.clone()) {
+                    pending.push(reference.path.clone());
+                }
+            }
+        }
+        found
+    }
+
+    /// The files referencing `file` directly, with the line of each reference
+    pub fn references_to(&self, file: &str) -> Vec<(&str, usize)> {
+        self.references
+            .iter()
+            .flat_map(|(user, references)| {
+                references.iter().filter(|r| r.path == file).map(move |r| (user.as_str(), r.line))
+            })
+            .collect()
+    }
+
+    /// Every file that needs `file`, directly or through other files
+    pub fn dependents(&self, file: &str) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let mut pending = vec![file.to_string()];
+
+        while let Some(next) = pending.pop() {
+            let users = self
+                .references
+                .iter()
+                .filter(|(_, references)| references.iter().any(|r| r.path == next))
+                .map(|(user, _)| user);
+            for user in users {
+                if user != file && found.insert(user.clone()) {
+                    pending.push(user.clone());
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Print everything a file needs, directly or through other files
+pub fn deps(file: String) {
+    let file = to_res_path(&file);
+    if !res_file(&file).exists() {
+        eprintln!("{} does not exist", file);
+        return;
+    }
+
+    let graph = DependencyGraph::build(Path::new("."));
+    for dependency in graph.dependencies(&file) {
+        if !dependency.starts_with("res://") || res_file(&dependency).exists() {
+            println!("{}", dependency);
+            continue;
+        }
+
+        let users = graph
+            .references_to(&dependency)
+            .iter()
+            .map(|(user, line)| format!("{}:{}", user, line))
+            .collect::<Vec<_>>();
+        println!("{} (missing, referenced by {})", dependency, users.join(", "));
+    }
+}
+
+/// Print everything that uses a file, directly or through other files
+pub fn rdeps(file: String) {
+    let file = to_res_path(&file);
+    for dependent in DependencyGraph::build(Path::new(".")).dependents(&file) {
+        println!("{}", dependent);
+    }
+}
+
+/// An edit made, or to be made, when rewriting references
+pub struct Edit {
+    pub file: PathBuf,
+    pub line: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// Replace every `res://` reference starting with one of the `from` prefixes
+/// by the matching `to` prefix in the referencing files of the project. A
+/// `from` that doesn't end with `/` only replaces whole paths, so a file
+/// isn't mistaken for the start of another one.
+/// Nothing is written when `dry_run` is set, the edits are returned either way
+pub fn rewrite_references(root: &Path, replacements: &[(String, String)], dry_run: bool) -> Result<Vec<Edit>> {
+    if replacements.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Replace in a single pass, longest prefix first, so a replaced
+    // reference is never rewritten again by another replacement
+    let mut sorted = replacements.iter().collect::<Vec<_>>();
+    sorted.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+    let pattern = sorted.iter().map(|(from, _)| regex::escape(from)).collect::<Vec<_>>().join("|");
+    let pattern = Regex::new(&pattern).unwrap();
+
+    let mut edits = Vec::new();
+
+    for file in project_files(root, &REFERENCING_EXTENSIONS) {
+        let data = match read_to_string(&file) {
+            Ok(d) => d,
+            Err(_) => continue,
+        };
+
+        let mut changed = false;
+        let mut lines = Vec::new();
+        for (n, line) in data.split('\n').enumerate() {
+            let rewritten = pattern.replace_all(line, |caps: &Captures| {
+                let from = &caps[0];
+                let next = line[caps.get(0).unwrap().end()..].chars().next();
+                if !from.ends_with('/') && next.map(is_path_char).unwrap_or(false) {
+                    return from.to_string();
+                }
+
+                let to = sorted.iter().find(|(f, _)| f == from).map(|(_, t)| t.clone()).unwrap();
+                edits.push(Edit {
+                    file: file.clone(),
+                    line: n + 1,
+                    from: from.to_string(),
+                    to: to.clone(),
+                });
+                to
+            });
+            changed |= rewritten != line;
+            lines.push(rewritten.into_owned());
+        }
+
+        if changed && !dry_run {
+            File::create(&file)?.write_all(lines.join("\n").as_bytes())?;
+        }
+    }
+
+    Ok(edits)
+}
+
+/// Recompute the relative references that a move from `from` to `to`
+/// changes: those of other files to the moved files, and those of the moved
+/// files to the files that stayed. Each is resolved from where its file was
+/// before the move and written relative to where it is after it, references
+/// that still resolve are left as they are.
+/// Nothing is written when `dry_run` is set, the files are then still
+/// where they were
+fn rewrite_relative_references(root: &Path, from: &str, to: &str, dry_run: bool) -> Result<Vec<Edit>> {
+    let mut edits = Vec::new();
+
+    for file in project_files(root, &REFERENCING_EXTENSIONS) {
+        let extension = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let (patterns, comment) = match extension {
+            "gd" => (vec![&*SCRIPT_EXTENDS, &*SCRIPT_LOAD], Some("#")),
+            ext if TEXT_RESOURCE_EXTENSIONS.contains(&ext) => (vec![&*EXT_RESOURCE_PATH], None),
+            _ => continue,
+        };
+
+        let current = res_path(file.strip_prefix(root).unwrap_or(&file));
+        let (before, after) = match dry_run {
+            true => (current.clone(), moved_path(&current, from, to).unwrap_or_else(|| current.clone())),
+            false => (moved_path(&current, to, from).unwrap_or_else(|| current.clone()), current.clone()),
+        };
+
+        let data = match read_to_string(&file) {
+            Ok(d) => d,
+            Err(_) => continue,
+        };
+
+        let mut changed = false;
+        let mut lines = Vec::new();
+        for (n, line) in data.split('\n').enumerate() {
+            let mut line = line.to_string();
+            if comment.map(|c| line.trim_start().starts_with(c)).unwrap_or(false) {
+                lines.push(line);
+                continue;
+            }
+
+            let mut paths = patterns
+                .iter()
+                .flat_map(|pattern| pattern.captures_iter(&line))
+                .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+                .map(|m| (m.start(), m.end()))
+                .filter(|&(start, end)| !line[start..end].contains("://"))
+                .collect::<Vec<_>>();
+            // Replace from the end of the line so the earlier positions stay valid
+            paths.sort_by(|a, b| b.0.cmp(&a.0));
+
+            for (start, end) in paths {
+                let written = line[start..end].to_string();
+                let target = resolve_res_path(&before, &written);
+                let target = moved_path(&target, from, to).unwrap_or(target);
+                if resolve_res_path(&after, &written) == target {
+                    continue;
+                }
+
+                let relative = relative_res_path(&after, &target);
+                line.replace_range(start..end, &relative);
+                edits.push(Edit {
+                    file: file.clone(),
+                    line: n + 1,
+                    from: written,
+                    to: relative,
+                });
+                changed = true;
+            }
+            lines.push(line);
+        }
+
+        if changed && !dry_run {
+            File::create(&file)?.write_all(lines.join("\n").as_bytes())?;
+        }
+    }
+
+    Ok(edits)
+}
+
+/// `path` after a move from `from` to `to`, both directories ending with
+/// `/` or files, or None if the move doesn't change it
+fn moved_path(path: &str, from: &str, to: &str) -> Option<String> {
+    match from.ends_with('/') {
+        true if path.starts_with(from) => Some(format!("{}{}", to, &path[from.len()..])),
+        false if path == from => Some(to.to_string()),
+        _ => None,
+    }
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '.' || c == '_' || c == '-' || c == '/'
+}
+
+/// Move a file or directory in the project, along with the `.import` file of
+/// each moved file, and update the references to it and the relative
+/// references of the moved files. With `dry_run` the moves and edits are
+/// only listed
+pub fn mv(from: String, to: String, dry_run: bool) {
+    let from = to_res_path(&from);
+    let mut to = to_res_path(&to);
+    let source = res_file(&from);
+    if !source.exists() {
+        eprintln!("{} does not exist", from);
+        return;
+    }
+
+    // Like mv, moving into an existing directory keeps the name
+    if res_file(&to).is_dir() {
+        let name = from.rsplit('/').next().unwrap_or_default();
+        to = format!("{}/{}", to.trim_end_matches('/'), name);
+    }
+    let target = res_file(&to);
+    if target.exists() {
+        eprintln!("{} already exists", to);
+        return;
+    }
+    if target.starts_with(&source) {
+        eprintln!("Can't move {} into itself", from);
+        return;
+    }
+
+    let replacement = match source.is_dir() {
+        true => (format!("{}/", from), format!("{}/", to)),
+        false => (from.clone(), to.clone()),
+    };
+
+    let sidecar = PathBuf::from(format!("{}.{}", source.display(), IMPORT_EXT));
+    let mut moves = vec![(source.clone(), target.clone())];
+    if sidecar.is_file() {
+        moves.push((sidecar, PathBuf::from(format!("{}.{}", target.display(), IMPORT_EXT))));
+    }
+
+    match dry_run {
+        true => {
+            for (source, target) in &moves {
+                println!("Would move {} to {}", source.display(), target.display());
+            }
+        }
+        false => {
+            if !move_together(&moves) {
+                return;
+            }
+        }
+    }
+
+    // The import files point back at the file they're for
+    if !dry_run {
+        let imports = match target.is_dir() {
+            true => project_files(&target, &[IMPORT_EXT]),
+            false => moves.iter().skip(1).map(|(_, target)| target.clone()).collect(),
+        };
+        for import in imports {
+            if let Err(e) = replace_in_file(&import, &replacement.0, &replacement.1) {
+                eprintln!("Failed to update {}: {}", import.display(), e);
+            }
+        }
+    }
+
+    let edits = rewrite_relative_references(Path::new("."), &replacement.0, &replacement.1, dry_run).and_then(|mut edits| {
+        edits.extend(rewrite_references(Path::new("."), &[replacement], dry_run)?);
+        Ok(edits)
+    });
+    match edits {
+        Ok(edits) => {
+            for edit in edits {
+                let action = if dry_run { "Would update" } else { "Updated" };
+                println!("{} {}:{}: {} -> {}", action, edit.file.display(), edit.line, edit.from, edit.to);
+            }
+        }
+        Err(e) => eprintln!("Failed to update references: {}", e),
+    }
+}
+
+/// Make each of the `moves`, from source to target. A file and its import
+/// settings are moved together or not at all, so when one fails the ones
+/// already made are moved back
+fn move_together(moves: &[(PathBuf, PathBuf)]) -> bool {
+    for (i, (source, target)) in moves.iter().enumerate() {
+        let moved = target.parent().map(create_dir_all).unwrap_or(Ok(())).and_then(|_| rename(source, target));
+        if let Err(e) = moved {
+            eprintln!("Failed to move {} to {}: {}", source.display(), target.display(), e);
+            for (source, target) in moves[..i].iter().rev() {
+                match rename(target, source) {
+                    Ok(_) => println!("Moved {} back to {}", target.display(), source.display()),
+                    Err(e) => eprintln!("Failed to move {} back to {}: {}", target.display(), source.display(), e),
+                }
+            }
+            return false;
+        }
+        println!("Moved {} to {}", source.display(), target.display());
+    }
+    true
+}
+
+fn replace_in_file(file: &Path, from: &str, to: &str) -> Result<()> {
+    let data = read_to_string(file)?;
+    if data.contains(from) {
+        File::create(file)?.write_all(data.replace(from, to).as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script_references(source: &str, res: &str) -> Vec<(String, usize)> {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("script.gd");
+        File::create(&file).unwrap().write_all(source.as_bytes()).unwrap();
+        references_at(&file, res).unwrap().into_iter().map(|r| (r.path, r.line)).collect()
+    }
+
+    #[test]
+    fn script_extends_by_path() {
+        let references = script_references("extends \"res://scripts/base.gd\"\n", "res://scripts/player.gd");
+        assert_eq!(references, vec![("res://scripts/base.gd".to_string(), 1)]);
+
+        let references = script_references("extends '../base.gd'\n", "res://scripts/enemies/slime.gd");
+        assert_eq!(references, vec![("res://scripts/base.gd".to_string(), 1)]);
+
+        let references = script_references("tool\nextends \"base.gd\"\n", "res://scripts/player.gd");
+        assert_eq!(references, vec![("res://scripts/base.gd".to_string(), 2)]);
+    }
+
+    #[test]
+    fn script_extends_by_class_is_not_a_reference() {
+        assert_eq!(script_references("extends KinematicBody2D\n", "res://player.gd"), vec![]);
+    }
+
+    #[test]
+    fn script_loads() {
+        let source = "extends \"base.gd\"\n\nconst Bullet = preload(\"bullet.tscn\")\n# var old = load(\"res://old.gd\")\n\nfunc _ready():\n    var data = load( \"res://data/level.tres\" )\n";
+        let references = script_references(source, "res://scripts/player.gd");
+        let expected = vec![
+            ("res://scripts/base.gd".to_string(), 1),
+            ("res://scripts/bullet.tscn".to_string(), 3),
+            ("res://data/level.tres".to_string(), 7),
+        ];
+        assert_eq!(references, expected);
+    }
+
+    #[test]
+    fn project_settings_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("project.godot");
+        let text = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/project.godot"));
+        File::create(&file).unwrap().write_all(text.as_bytes()).unwrap();
+
+        let references = references_at(&file, "res://project.godot").unwrap();
+        let paths = references.iter().map(|r| (r.path.as_str(), r.line)).collect::<Vec<_>>();
+        assert!(paths.contains(&("res://Main.tscn", 24)));
+        assert!(paths.contains(&("res://global.gd", 29)));
+        assert!(paths.contains(&("res://addons/tool/plugin.cfg", 38)));
+    }
+
+    fn write(dir: &Path, file: &str, text: &str) -> PathBuf {
+        let path = dir.join(file);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(&path).unwrap().write_all(text.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn relative_references_follow_a_move() {
+        // The project after moving scripts/player.gd to scripts/sub/player.gd
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let player = write(
+            root,
+            "scripts/sub/player.gd",
+            "extends \"base.gd\"\nconst U = preload(\"./util.gd\")\nvar a = load(\"res://scripts/util.gd\")\n",
+        );
+        let helper = write(root, "scripts/sub/helper.gd", "const P = preload(\"../player.gd\")\nconst H = preload(\"helper.gd\")\n");
+        let scene = write(
+            root,
+            "scenes/main.tscn",
+            "[gd_scene load_steps=2 format=2]\n\n[ext_resource path=\"../scripts/player.gd\" type=\"Script\" id=1]\n",
+        );
+        write(root, "scripts/base.gd", "extends Node\n");
+        write(root, "scripts/util.gd", "extends Node\n");
+
+        let edits = rewrite_relative_references(root, "res://scripts/player.gd", "res://scripts/sub/player.gd", false).unwrap();
+        assert_eq!(edits.len(), 4);
+        assert_eq!(
+            read_to_string(&player).unwrap(),
+            "extends \"../base.gd\"\nconst U = preload(\"../util.gd\")\nvar a = load(\"res://scripts/util.gd\")\n"
+        );
+        assert_eq!(read_to_string(&helper).unwrap(), "const P = preload(\"player.gd\")\nconst H = preload(\"helper.gd\")\n");
+        assert!(read_to_string(&scene).unwrap().contains("path=\"../scripts/sub/player.gd\""));
+    }
+
+    #[test]
+    fn relative_references_of_a_moved_directory() {
+        // The project after moving scripts/sub/ to lib/, dry runs see it before
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let player = write(root, "lib/player.gd", "extends \"../base.gd\"\nconst H = preload(\"helper.gd\")\n");
+        write(root, "lib/helper.gd", "extends Node\n");
+        write(root, "scripts/base.gd", "extends Node\n");
+
+        let edits = rewrite_relative_references(root, "res://scripts/sub/", "res://lib/", false).unwrap();
+        let edits = edits.iter().map(|e| (e.from.as_str(), e.to.as_str())).collect::<Vec<_>>();
+        assert_eq!(edits, vec![("../base.gd", "../scripts/base.gd")]);
+        assert_eq!(read_to_string(&player).unwrap(), "extends \"../scripts/base.gd\"\nconst H = preload(\"helper.gd\")\n");
+
+        let edits = rewrite_relative_references(root, "res://lib/", "res://scripts/sub/", true).unwrap();
+        let edits = edits.iter().map(|e| (e.from.as_str(), e.to.as_str())).collect::<Vec<_>>();
+        assert_eq!(edits, vec![("../scripts/base.gd", "../base.gd")]);
+        assert_eq!(read_to_string(&player).unwrap(), "extends \"../scripts/base.gd\"\nconst H = preload(\"helper.gd\")\n");
+    }
+
+    #[test]
+    fn failed_import_move_moves_the_file_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let image = write(root, "art/a.png", "png");
+        let import = write(root, "art/a.png.import", "source_file=\"res://art/a.png\"\n");
+        // The import file can't replace a directory
+        write(root, "img/b.png.import/keep", "");
+
+        let moves = vec![
+            (image.clone(), root.join("img/b.png")),
+            (import.clone(), root.join("img/b.png.import")),
+        ];
+        assert!(!move_together(&moves));
+        assert!(image.is_file());
+        assert!(import.is_file());
+        assert!(!root.join("img/b.png").exists());
+
+        let moves = vec![(image.clone(), root.join("img/c.png")), (import, root.join("img/c.png.import"))];
+        assert!(move_together(&moves));
+        assert!(!image.exists());
+        assert!(root.join("img/c.png.import").is_file());
+    }
+
+    #[test]
+    fn moved_paths() {
+        assert_eq!(moved_path("res://a/b.gd", "res://a/b.gd", "res://c.gd"), Some("res://c.gd".to_string()));
+        assert_eq!(moved_path("res://a/b.gd.import", "res://a/b.gd", "res://c.gd"), None);
+        assert_eq!(moved_path("res://a/b/c.gd", "res://a/", "res://x/"), Some("res://x/b/c.gd".to_string()));
+        assert_eq!(moved_path("res://ab/c.gd", "res://a/", "res://x/"), None);
+    }
+
+    #[test]
+    fn resolve_and_relativise() {
+        assert_eq!(resolve_res_path("res://a/b/c.gd", "../d.gd"), "res://a/d.gd");
+        assert_eq!(resolve_res_path("res://a/b/c.gd", "./d/e.gd"), "res://a/b/d/e.gd");
+        assert_eq!(resolve_res_path("res://a/c.gd", "res://x.gd"), "res://x.gd");
+        assert_eq!(resolve_res_path("res://a/c.gd", "user://save.tres"), "user://save.tres");
+        assert_eq!(relative_res_path("res://a/b/c.gd", "res://a/d.gd"), "../d.gd");
+        assert_eq!(relative_res_path("res://a/c.gd", "res://a/b/d.gd"), "b/d.gd");
+    }
+}
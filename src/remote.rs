@@ -0,0 +1,252 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+
+use log::{info, warn};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::Url;
+
+use crate::config::RemoteConfig;
+use crate::credentials::Credentials;
+use crate::errors::{Error, Result};
+
+/// Longest wait between two retries
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+/// Fetches files from remote package sources.
+///
+/// Every fetch takes a list of base urls: the primary source followed by
+/// any mirrors. Each url is retried with exponential backoff on transient
+/// failures before moving on to the next one.
+///
+/// Plain http urls are refused unless `allow_insecure` is set, redirects to
+/// another host (or from https to http) are not followed unless the config
+/// allows it, and downloads are capped per file and per package.
+pub struct Fetcher {
+    client: Client,
+    retries: u32,
+    backoff: Duration,
+    credentials: Credentials,
+    allow_insecure: bool,
+    max_file_size: u64,
+    max_package_size: u64,
+    package_size: Cell<u64>,
+}
+
+impl Fetcher {
+    pub fn new(config: &RemoteConfig, credentials: Credentials, allow_insecure: bool) -> Result<Self> {
+        let allow_cross_host = config.allow_cross_host_redirects;
+        let policy = Policy::custom(move |attempt| {
+            let previous = attempt.previous();
+            let original = &previous[0];
+            if previous.len() > 10 {
+                attempt.error("too many redirects")
+            } else if !allow_cross_host && attempt.url().host_str() != original.host_str() {
+                attempt.stop()
+            } else if !allow_insecure && attempt.url().scheme() != "https" {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        });
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout))
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .redirect(policy)
+            .user_agent(concat!("gdt/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self {
+            client,
+            retries: config.retries,
+            backoff: Duration::from_millis(config.backoff),
+            credentials,
+            allow_insecure,
+            max_file_size: config.max_file_size,
+            max_package_size: config.max_package_size,
+            package_size: Cell::new(0),
+        })
+    }
+
+    /// Reset the per package download total
+    pub fn start_package(&self) {
+        self.package_size.set(0);
+    }
+
+    /// Fetch `fragment` relative to each base url in turn and hand the first
+    /// successful response to `read`
+    fn fetch<T, F>(&self, roots: &[String], fragment: &str, read: F) -> Result<T>
+    where
+        F: Fn(Response) -> Result<T>,
+    {
+        let mut last_err = Error::NoSources;
+
+        for root in roots {
+            let url = format!("{}{}", root, fragment);
+            match self.fetch_with_retries(&url, &read) {
+                Ok(value) => return Ok(value),
+                Err(e) if e.is_policy_violation() => return Err(e),
+                Err(e) => {
+                    warn!("failed to fetch {}: {}", redact(&url), e);
+                    last_err = e;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    pub fn text(&self, roots: &[String], fragment: &str) -> Result<String> {
+        self.fetch(roots, fragment, |response| {
+            let mut buf = Vec::new();
+            self.read_limited(response, &mut buf)?;
+            String::from_utf8(buf).map_err(|e| Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+        })
+    }
+
+    pub fn download(&self, roots: &[String], fragment: &str, dst: &PathBuf) -> Result<()> {
+        self.fetch(roots, fragment, |response| {
+            let mut file = File::create(dst)?;
+            self.read_limited(response, &mut file)?;
+            Ok(())
+        })
+    }
+
+    /// Copy the response body into `out`, failing as soon as either the
+    /// file or the package size limit is exceeded
+    fn read_limited<W: Write>(&self, response: Response, out: &mut W) -> Result<()> {
+        let url = redact(response.url().as_str());
+        let remaining = self.max_package_size.saturating_sub(self.package_size.get());
+        let limit = self.max_file_size.min(remaining);
+
+        let too_large = |size: u64| match size > self.max_file_size {
+            true => Error::FileTooLarge { url: url.clone(), limit: self.max_file_size },
+            false => Error::PackageTooLarge { limit: self.max_package_size },
+        };
+
+        if let Some(len) = response.content_length() {
+            if len > limit {
+                return Err(too_large(len));
+            }
+        }
+
+        // Never trust the content length, read one byte past the limit to detect overruns.
+        // Reading and writing are kept apart so only read errors are retried
+        let mut body = response.take(limit + 1);
+        let mut buf = [0; 8192];
+        let mut copied = 0;
+        loop {
+            let n = match body.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(Error::Body { url, error }),
+            };
+            out.write_all(&buf[..n])?;
+            copied += n as u64;
+        }
+        if copied > limit {
+            return Err(too_large(copied));
+        }
+
+        self.package_size.set(self.package_size.get() + copied);
+        Ok(())
+    }
+
+    fn fetch_with_retries<T, F>(&self, url: &str, read: &F) -> Result<T>
+    where
+        F: Fn(Response) -> Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            let res = self.get_once(url).and_then(read);
+            match res {
+                Err(ref e) if is_transient(e) && attempt < self.retries => {
+                    let delay = self.backoff.checked_mul(2u32.saturating_pow(attempt)).unwrap_or(MAX_BACKOFF).min(MAX_BACKOFF);
+                    info!("retrying {} in {:?} ({})", redact(url), delay, e);
+                    sleep(delay);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn get_once(&self, url: &str) -> Result<Response> {
+        let parsed = Url::parse(url).map_err(|_| Error::InvalidUrl(redact(url)))?;
+        if parsed.scheme() != "https" && !self.allow_insecure {
+            return Err(Error::Insecure(redact(url)));
+        }
+
+        let mut request = self.client.get(parsed.clone());
+        if let Some(host) = parsed.host_str() {
+            request = self.credentials.apply(host, request);
+        }
+
+        let response = request.send()?;
+
+        match response.status().as_u16() {
+            200..=299 => Ok(response),
+            // Only redirects rejected by the policy end up here
+            300..=399 => Err(Error::Redirect {
+                from: redact(url),
+                to: response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|l| l.to_str().ok())
+                    .map(redact)
+                    .unwrap_or_default(),
+            }),
+            status => Err(Error::Http {
+                url: redact(response.url().as_str()),
+                status,
+            }),
+        }
+    }
+}
+
+fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Http { status, .. } => *status >= 500 || *status == 429,
+        // Connection and body errors, as opposed to bad urls or redirect loops
+        Error::Reqwest(e) => !(e.is_builder() || e.is_redirect() || e.is_status()),
+        // The connection dropping while the body is read, not a body that can't be decoded
+        Error::Body { error, .. } => error.kind() != io::ErrorKind::InvalidData,
+        _ => false,
+    }
+}
+
+/// Strip any user info from a url so it can be shown to the user
+pub fn redact(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_bodies_are_retried() {
+        let body = |kind| Error::Body {
+            url: "https://example.com/a.gd".to_string(),
+            error: io::Error::new(kind, "body"),
+        };
+        assert!(is_transient(&body(io::ErrorKind::ConnectionReset)));
+        assert!(is_transient(&body(io::ErrorKind::UnexpectedEof)));
+        assert!(!is_transient(&body(io::ErrorKind::InvalidData)));
+        assert!(!is_transient(&Error::Io(io::Error::new(io::ErrorKind::PermissionDenied, "write"))));
+    }
+}